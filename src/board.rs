@@ -5,6 +5,7 @@ use crate::{
     },
     magic_gen::{BISHOP_DELTAS, ROOK_DELTAS},
     moves::{Flags, Move},
+    zobrist::ZOBRIST,
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -55,6 +56,31 @@ impl Piece {
         moves
     }
 
+    /// The lowercase FEN letter of the piece.
+    pub fn to_char(self) -> char {
+        match self {
+            Piece::Pawn => 'p',
+            Piece::Knight => 'n',
+            Piece::Bishop => 'b',
+            Piece::Rook => 'r',
+            Piece::Queen => 'q',
+            Piece::King => 'k',
+        }
+    }
+
+    /// The piece of a lowercase FEN letter.
+    pub fn from_char(c: char) -> Option<Piece> {
+        match c {
+            'p' => Some(Piece::Pawn),
+            'n' => Some(Piece::Knight),
+            'b' => Some(Piece::Bishop),
+            'r' => Some(Piece::Rook),
+            'q' => Some(Piece::Queen),
+            'k' => Some(Piece::King),
+            _ => None,
+        }
+    }
+
     pub fn name(&self) -> &'static str {
         match self {
             Piece::Pawn => "Pawn",
@@ -67,30 +93,26 @@ impl Piece {
     }
 }
 
+/// Everything `unmake_move` needs to restore the position before a move.
 #[derive(Clone, Copy)]
 pub struct Undo {
+    moved: Piece,
     captured: Option<(Square, Piece, Color)>,
     castling_rights: u8,
     ep_square: Option<Square>,
     halfmove_clock: u8,
     fullmove_number: u16,
+    /// Zobrist hash of the position before the move.
+    pub hash: u64,
+    pawn_key: u64,
 }
 
 #[derive(Clone)]
 pub struct Board {
-    pub white_pawns: BitBoard,
-    pub white_knights: BitBoard,
-    pub white_bishops: BitBoard,
-    pub white_rooks: BitBoard,
-    pub white_queens: BitBoard,
-    pub white_king: BitBoard,
-
-    pub black_pawns: BitBoard,
-    pub black_knights: BitBoard,
-    pub black_bishops: BitBoard,
-    pub black_rooks: BitBoard,
-    pub black_queens: BitBoard,
-    pub black_king: BitBoard,
+    /// Piece bitboards, indexed by `Color` and `Piece`.
+    pieces: [[BitBoard; 6]; 2],
+    /// What stands on each square, kept in sync with `pieces`.
+    mailbox: [Option<(Piece, Color)>; 64],
 
     pub white_occupied: BitBoard,
     pub black_occupied: BitBoard,
@@ -105,6 +127,8 @@ pub struct Board {
     pub fullmove_number: u16,
 
     pub zobrist_hash: u64,
+    /// Zobrist key of the pawns alone, for the pawn hash table.
+    pub pawn_key: u64,
 
     pub history: Vec<Undo>,
 }
@@ -112,193 +136,104 @@ pub struct Board {
 impl Board {
     pub fn default() -> Board {
         // standart fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1
-        let white_pawns = BitBoard(0x000000000000FF00);
-        let white_knights = BitBoard(0x0000000000000042);
-        let white_bishops = BitBoard(0x0000000000000024);
-        let white_rooks = BitBoard(0x0000000000000081);
-        let white_queens = BitBoard(0x0000000000000008);
-        let white_king = BitBoard(0x0000000000000010);
-
-        let black_pawns = BitBoard(0x00FF000000000000);
-        let black_knights = BitBoard(0x4200000000000000);
-        let black_bishops = BitBoard(0x2400000000000000);
-        let black_rooks = BitBoard(0x8100000000000000);
-        let black_queens = BitBoard(0x0800000000000000);
-        let black_king = BitBoard(0x1000000000000000);
-
-        let white_occupied =
-            white_pawns | white_knights | white_bishops | white_rooks | white_queens | white_king;
-        let black_occupied =
-            black_pawns | black_knights | black_bishops | black_rooks | black_queens | black_king;
-
-        let occupied = white_occupied | black_occupied;
-        let empty = !occupied;
+        let setup = [
+            (Piece::Pawn, 0x000000000000FF00),
+            (Piece::Knight, 0x0000000000000042),
+            (Piece::Bishop, 0x0000000000000024),
+            (Piece::Rook, 0x0000000000000081),
+            (Piece::Queen, 0x0000000000000008),
+            (Piece::King, 0x0000000000000010),
+        ];
+
+        let mut board = Board::empty();
+        for (piece, white) in setup {
+            for square in BitBoard(white) {
+                board.add_piece(square, piece, Color::White);
+                // Black mirrors White across the middle of the board.
+                board.add_piece(Square::from_index(square as u8 ^ 56), piece, Color::Black);
+            }
+        }
+
+        board.castling_rights = 0b1111;
+        board.zobrist_hash = board.compute_hash();
+        board.pawn_key = board.compute_pawn_key();
+        board
+    }
 
+    /// A board without any pieces, White to move.
+    pub fn empty() -> Board {
         Board {
-            white_pawns,
-            white_knights,
-            white_bishops,
-            white_rooks,
-            white_queens,
-            white_king,
-            black_pawns,
-            black_knights,
-            black_bishops,
-            black_rooks,
-            black_queens,
-            black_king,
-            white_occupied,
-            black_occupied,
-            occupied,
-            empty,
+            pieces: [[BitBoard::EMPTY; 6]; 2],
+            mailbox: [None; 64],
+            white_occupied: BitBoard::EMPTY,
+            black_occupied: BitBoard::EMPTY,
+            occupied: BitBoard::EMPTY,
+            empty: !BitBoard::EMPTY,
             turn: Color::White,
-            castling_rights: 0b1111,
+            castling_rights: 0,
             en_passant_square: None,
             halfmove_clock: 0,
             fullmove_number: 1,
             zobrist_hash: 0,
+            pawn_key: 0,
             history: Vec::new(),
         }
     }
 
+    pub fn pieces_of(&self, color: Color, piece: Piece) -> BitBoard {
+        self.pieces[color as usize][piece as usize]
+    }
+
+    pub fn occupied_by(&self, color: Color) -> BitBoard {
+        match color {
+            Color::White => self.white_occupied,
+            Color::Black => self.black_occupied,
+        }
+    }
+
     pub fn make_move(&mut self, mv: &Move) {
+        // An en passant capture removes the pawn behind the target square.
+        let captured_sq = if mv.flags == Flags::EnPassant {
+            match self.turn {
+                Color::White => Square::from_index(mv.to as u8 - 8),
+                Color::Black => Square::from_index(mv.to as u8 + 8),
+            }
+        } else {
+            mv.to
+        };
+
         let undo = Undo {
+            moved: mv.piece,
             captured: mv
                 .captured_piece
-                .map(|pc| (mv.to, pc, self.turn.opposite())),
+                .map(|pc| (captured_sq, pc, self.turn.opposite())),
             castling_rights: self.castling_rights,
             ep_square: self.en_passant_square,
             halfmove_clock: self.halfmove_clock,
             fullmove_number: self.fullmove_number,
+            hash: self.zobrist_hash,
+            pawn_key: self.pawn_key,
         };
 
         self.history.push(undo);
 
-        let from_bit = mv.from.bb();
-        let to_bit = mv.to.bb();
-
-        match self.turn {
-            Color::White => {
-                if self.white_pawns & from_bit != BitBoard::EMPTY {
-                    self.white_pawns &= !from_bit;
-                } else if self.white_knights & from_bit != BitBoard::EMPTY {
-                    self.white_knights &= !from_bit;
-                } else if self.white_bishops & from_bit != BitBoard::EMPTY {
-                    self.white_bishops &= !from_bit;
-                } else if self.white_rooks & from_bit != BitBoard::EMPTY {
-                    self.white_rooks &= !from_bit;
-                } else if self.white_queens & from_bit != BitBoard::EMPTY {
-                    self.white_queens &= !from_bit;
-                } else if self.white_king & from_bit != BitBoard::EMPTY {
-                    self.white_king &= !from_bit;
-                }
+        // Take the old castling rights and en passant file out of the hash;
+        // the new ones are hashed in once they are known.
+        self.zobrist_hash ^= ZOBRIST.castling[self.castling_rights as usize]
+            ^ ZOBRIST.en_passant(self.en_passant_square);
 
-                if self.black_occupied & to_bit != BitBoard::EMPTY {
-                    if self.black_pawns & to_bit != BitBoard::EMPTY {
-                        self.black_pawns &= !to_bit;
-                    } else if self.black_knights & to_bit != BitBoard::EMPTY {
-                        self.black_knights &= !to_bit;
-                    } else if self.black_bishops & to_bit != BitBoard::EMPTY {
-                        self.black_bishops &= !to_bit;
-                    } else if self.black_rooks & to_bit != BitBoard::EMPTY {
-                        self.black_rooks &= !to_bit;
-                    } else if self.black_queens & to_bit != BitBoard::EMPTY {
-                        self.black_queens &= !to_bit;
-                    } else if self.black_king & to_bit != BitBoard::EMPTY {
-                        self.black_king &= !to_bit;
-                    }
-                }
+        self.toggle_piece_key(self.turn, mv.piece, mv.from);
+        self.toggle_piece_key(self.turn, mv.promotion.unwrap_or(mv.piece), mv.to);
 
-                if let Some(promotion_piece) = mv.promotion {
-                    match promotion_piece {
-                        Piece::Queen => self.white_queens |= to_bit,
-                        Piece::Rook => self.white_rooks |= to_bit,
-                        Piece::Bishop => self.white_bishops |= to_bit,
-                        Piece::Knight => self.white_knights |= to_bit,
-                        _ => unreachable!(
-                            "Pawn can only be promoted to Queen, Rook, Bishop, or Knight"
-                        ),
-                    }
-                } else {
-                    match mv.piece {
-                        Piece::Pawn => self.white_pawns |= to_bit,
-                        Piece::Knight => self.white_knights |= to_bit,
-                        Piece::Bishop => self.white_bishops |= to_bit,
-                        Piece::Rook => self.white_rooks |= to_bit,
-                        Piece::Queen => self.white_queens |= to_bit,
-                        Piece::King => self.white_king |= to_bit,
-                    }
-                }
-            }
-            Color::Black => {
-                if self.black_pawns & from_bit != BitBoard::EMPTY {
-                    self.black_pawns &= !from_bit;
-                } else if self.black_knights & from_bit != BitBoard::EMPTY {
-                    self.black_knights &= !from_bit;
-                } else if self.black_bishops & from_bit != BitBoard::EMPTY {
-                    self.black_bishops &= !from_bit;
-                } else if self.black_rooks & from_bit != BitBoard::EMPTY {
-                    self.black_rooks &= !from_bit;
-                } else if self.black_queens & from_bit != BitBoard::EMPTY {
-                    self.black_queens &= !from_bit;
-                } else if self.black_king & from_bit != BitBoard::EMPTY {
-                    self.black_king &= !from_bit;
-                }
-
-                if self.white_occupied & to_bit != BitBoard::EMPTY {
-                    if self.white_pawns & to_bit != BitBoard::EMPTY {
-                        self.white_pawns &= !to_bit;
-                    } else if self.white_knights & to_bit != BitBoard::EMPTY {
-                        self.white_knights &= !to_bit;
-                    } else if self.white_bishops & to_bit != BitBoard::EMPTY {
-                        self.white_bishops &= !to_bit;
-                    } else if self.white_rooks & to_bit != BitBoard::EMPTY {
-                        self.white_rooks &= !to_bit;
-                    } else if self.white_queens & to_bit != BitBoard::EMPTY {
-                        self.white_queens &= !to_bit;
-                    } else if self.white_king & to_bit != BitBoard::EMPTY {
-                        self.white_king &= !to_bit;
-                    }
-                }
-
-                if let Some(promotion_piece) = mv.promotion {
-                    match promotion_piece {
-                        Piece::Queen => self.black_queens |= to_bit,
-                        Piece::Rook => self.black_rooks |= to_bit,
-                        Piece::Bishop => self.black_bishops |= to_bit,
-                        Piece::Knight => self.black_knights |= to_bit,
-                        _ => unreachable!(
-                            "Pawn can only be promoted to Queen, Rook, Bishop, or Knight"
-                        ),
-                    }
-                } else {
-                    match mv.piece {
-                        Piece::Pawn => self.black_pawns |= to_bit,
-                        Piece::Knight => self.black_knights |= to_bit,
-                        Piece::Bishop => self.black_bishops |= to_bit,
-                        Piece::Rook => self.black_rooks |= to_bit,
-                        Piece::Queen => self.black_queens |= to_bit,
-                        Piece::King => self.black_king |= to_bit,
-                    }
-                }
-            }
+        // En passant captures go through `delete_piece`, which hashes them.
+        if let (Some(captured), false) = (mv.captured_piece, mv.flags == Flags::EnPassant) {
+            self.toggle_piece_key(self.turn.opposite(), captured, mv.to);
         }
 
-        self.white_occupied = self.white_pawns
-            | self.white_knights
-            | self.white_bishops
-            | self.white_rooks
-            | self.white_queens
-            | self.white_king;
-        self.black_occupied = self.black_pawns
-            | self.black_knights
-            | self.black_bishops
-            | self.black_rooks
-            | self.black_queens
-            | self.black_king;
-
-        self.occupied = self.white_occupied | self.black_occupied;
-        self.empty = !self.occupied;
+        // En passant leaves the target square empty; its pawn is removed below.
+        self.take(mv.to);
+        self.take(mv.from);
+        self.put(mv.to, mv.promotion.unwrap_or(mv.piece), self.turn);
 
         if mv.piece == Piece::Pawn && (mv.to as i8 - mv.from as i8).abs() == 16 {
             // two‑square pawn push → record the square “behind” the pawn
@@ -309,10 +244,10 @@ impl Board {
             self.en_passant_square = None;
         }
 
-        if mv.piece == Piece::Pawn || (self.occupied.0 & to_bit.0) != 0 {
+        if mv.piece == Piece::Pawn || mv.captured_piece.is_some() {
             self.halfmove_clock = 0;
         } else {
-            self.halfmove_clock += 1;
+            self.halfmove_clock = self.halfmove_clock.saturating_add(1);
         }
 
         if self.turn == Color::Black {
@@ -369,6 +304,15 @@ impl Board {
             }
         }
 
+        // Capturing a rook on its original square also takes away that side's right.
+        match mv.to {
+            Square::A1 => self.castling_rights &= !W_QUEENSIDE_RIGHTS,
+            Square::H1 => self.castling_rights &= !W_KINGSIDE_RIGHTS,
+            Square::A8 => self.castling_rights &= !B_QUEENSIDE_RIGHTS,
+            Square::H8 => self.castling_rights &= !B_KINGSIDE_RIGHTS,
+            _ => {}
+        }
+
         // TODO: Handle special moves like castling and en passant captures explicitly.
 
         if mv.flags == Flags::EnPassant {
@@ -384,13 +328,31 @@ impl Board {
             }
         }
 
-        // TODO: Update Zobrist hash (incrementally)
+        self.zobrist_hash ^= ZOBRIST.castling[self.castling_rights as usize]
+            ^ ZOBRIST.en_passant(self.en_passant_square)
+            ^ ZOBRIST.side_to_move;
+
         self.turn = self.turn.opposite();
+
+        #[cfg(feature = "hash-check")]
+        {
+            assert_eq!(
+                self.zobrist_hash,
+                self.compute_hash(),
+                "hash diverged after {:?}",
+                mv
+            );
+            assert_eq!(
+                self.pawn_key,
+                self.compute_pawn_key(),
+                "pawn key diverged after {:?}",
+                mv
+            );
+        }
     }
 
-    /// Reverts a given move, restoring the board to its previous state.
-    /// This is a simplified version and will need to be expanded to handle all move types.
-    /// This requires storing more information about the move (e.g., captured piece type).
+    /// Reverts `mv`, which must be the last move made, restoring the board
+    /// exactly, hashes included.
     pub fn unmake_move(&mut self, mv: &Move) {
         let undo = self
             .history
@@ -401,264 +363,138 @@ impl Board {
         self.en_passant_square = undo.ep_square;
         self.halfmove_clock = undo.halfmove_clock;
         self.fullmove_number = undo.fullmove_number;
+        self.zobrist_hash = undo.hash;
+        self.pawn_key = undo.pawn_key;
+
+        let color = self.turn.opposite();
+
+        self.take(mv.to);
+        self.put(mv.from, undo.moved, color);
 
-        let from_bit = mv.from.bb();
-        let to_bit = mv.to.bb();
-
-        match self.turn.opposite() {
-            Color::White => {
-                // White's piece was moved
-                // Remove piece from 'to' square (handle promotion reversal)
-                if mv.promotion.is_some() {
-                    match mv.promotion.unwrap() {
-                        Piece::Queen => self.white_queens &= !to_bit,
-                        Piece::Rook => self.white_rooks &= !to_bit,
-                        Piece::Bishop => self.white_bishops &= !to_bit,
-                        Piece::Knight => self.white_knights &= !to_bit,
-                        _ => unreachable!(),
-                    }
-
-                    self.white_pawns |= from_bit; // Original piece was a pawn
-                } else {
-                    // If no promotion, move the original piece type back
-                    match mv.piece {
-                        Piece::Pawn => {
-                            self.white_pawns &= !to_bit;
-                            self.white_pawns |= from_bit;
-                        }
-                        Piece::Knight => {
-                            self.white_knights &= !to_bit;
-                            self.white_knights |= from_bit;
-                        }
-                        Piece::Bishop => {
-                            self.white_bishops &= !to_bit;
-                            self.white_bishops |= from_bit;
-                        }
-                        Piece::Rook => {
-                            self.white_rooks &= !to_bit;
-                            self.white_rooks |= from_bit;
-                        }
-                        Piece::Queen => {
-                            self.white_queens &= !to_bit;
-                            self.white_queens |= from_bit;
-                        }
-                        Piece::King => {
-                            self.white_king &= !to_bit;
-                            self.white_king |= from_bit;
-                        }
-                    }
-                }
-            }
-            Color::Black => {
-                if mv.promotion.is_some() {
-                    match mv.promotion.unwrap() {
-                        Piece::Queen => self.black_queens &= !to_bit,
-                        Piece::Rook => self.black_rooks &= !to_bit,
-                        Piece::Bishop => self.black_bishops &= !to_bit,
-                        Piece::Knight => self.black_knights &= !to_bit,
-                        _ => unreachable!(),
-                    }
-                    self.black_pawns |= from_bit; // Original piece was a pawn
-                } else {
-                    match mv.piece {
-                        Piece::Pawn => {
-                            self.black_pawns &= !to_bit;
-                            self.black_pawns |= from_bit;
-                        }
-                        Piece::Knight => {
-                            self.black_knights &= !to_bit;
-                            self.black_knights |= from_bit;
-                        }
-                        Piece::Bishop => {
-                            self.black_bishops &= !to_bit;
-                            self.black_bishops |= from_bit;
-                        }
-                        Piece::Rook => {
-                            self.black_rooks &= !to_bit;
-                            self.black_rooks |= from_bit;
-                        }
-                        Piece::Queen => {
-                            self.black_queens &= !to_bit;
-                            self.black_queens |= from_bit;
-                        }
-                        Piece::King => {
-                            self.black_king &= !to_bit;
-                            self.black_king |= from_bit;
-                        }
-                    }
-                }
-            }
-        }
         if let Some((sq, piece, color)) = undo.captured {
-            let bb = sq.bb();
-
-            match (piece, color) {
-                (Piece::Pawn, Color::White) => self.white_pawns |= bb,
-                (Piece::Knight, Color::White) => self.white_knights |= bb,
-                (Piece::Bishop, Color::White) => self.white_bishops |= bb,
-                (Piece::Rook, Color::White) => self.white_rooks |= bb,
-                (Piece::Queen, Color::White) => self.white_queens |= bb,
-                (Piece::Pawn, Color::Black) => self.black_pawns |= bb,
-                (Piece::Knight, Color::Black) => self.black_knights |= bb,
-                (Piece::Bishop, Color::Black) => self.black_bishops |= bb,
-                (Piece::Rook, Color::Black) => self.black_rooks |= bb,
-                (Piece::Queen, Color::Black) => self.black_queens |= bb,
-                _ => unreachable!("Invalid captured piece: {:?}", piece),
-            }
+            self.put(sq, piece, color);
         }
 
         if mv.flags == Flags::Castling {
-            match (mv.piece, mv.to) {
-                (Piece::King, Square::G1) => {
-                    // white kingside: rook f1 -> h1
-                    self.white_rooks &= !Square::F1.bb();
-                    self.white_rooks |= Square::H1.bb();
-                }
-                (Piece::King, Square::C1) => {
-                    self.white_rooks &= !Square::D1.bb();
-                    self.white_rooks |= Square::A1.bb();
-                }
-                (Piece::King, Square::G8) => {
-                    self.black_rooks &= !Square::F8.bb();
-                    self.black_rooks |= Square::H8.bb();
-                }
-                (Piece::King, Square::C8) => {
-                    self.black_rooks &= !Square::D8.bb();
-                    self.black_rooks |= Square::A8.bb();
-                }
-                _ => {}
-            }
+            let (rook_from, rook_to) = match mv.to {
+                Square::G1 => (Square::H1, Square::F1),
+                Square::C1 => (Square::A1, Square::D1),
+                Square::G8 => (Square::H8, Square::F8),
+                _ => (Square::A8, Square::D8),
+            };
+            self.take(rook_to);
+            self.put(rook_from, Piece::Rook, color);
+        }
+
+        self.turn = self.turn.opposite();
+
+        #[cfg(feature = "hash-check")]
+        {
+            assert_eq!(
+                self.zobrist_hash,
+                self.compute_hash(),
+                "hash diverged undoing {:?}",
+                mv
+            );
+            assert_eq!(
+                self.pawn_key,
+                self.compute_pawn_key(),
+                "pawn key diverged undoing {:?}",
+                mv
+            );
+        }
+    }
+
+    /// Passes the turn without moving, for null-move pruning. The halfmove
+    /// clock restarts so no repetition is detected across the null move.
+    pub fn make_null_move(&mut self) {
+        self.history.push(Undo {
+            moved: Piece::King,
+            captured: None,
+            castling_rights: self.castling_rights,
+            ep_square: self.en_passant_square,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            hash: self.zobrist_hash,
+            pawn_key: self.pawn_key,
+        });
+
+        self.zobrist_hash ^= ZOBRIST.en_passant(self.en_passant_square) ^ ZOBRIST.side_to_move;
+        self.en_passant_square = None;
+        self.halfmove_clock = 0;
+        if self.turn == Color::Black {
+            self.fullmove_number += 1;
         }
+        self.turn = self.turn.opposite();
+    }
 
-        self.white_occupied = self.white_pawns
-            | self.white_knights
-            | self.white_bishops
-            | self.white_rooks
-            | self.white_queens
-            | self.white_king;
-        self.black_occupied = self.black_pawns
-            | self.black_knights
-            | self.black_bishops
-            | self.black_rooks
-            | self.black_queens
-            | self.black_king;
-        self.occupied = self.white_occupied | self.black_occupied;
-        self.empty = !self.occupied;
+    /// Reverts `make_null_move`, which must be the last move made.
+    pub fn unmake_null_move(&mut self) {
+        let undo = self
+            .history
+            .pop()
+            .expect("unmake_null_move: no undo information");
 
+        self.en_passant_square = undo.ep_square;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.fullmove_number = undo.fullmove_number;
+        self.zobrist_hash = undo.hash;
         self.turn = self.turn.opposite();
     }
 
     pub fn add_piece(&mut self, square: Square, piece: Piece, color: Color) {
-        let square_bit = square.bb();
+        self.put(square, piece, color);
+        self.toggle_piece_key(color, piece, square);
+    }
+
+    pub fn delete_piece(&mut self, square: Square) {
+        if let Some((piece, color)) = self.take(square) {
+            self.toggle_piece_key(color, piece, square);
+        }
+    }
+
+    /// Places a piece on an empty square, leaving the hash keys alone.
+    fn put(&mut self, square: Square, piece: Piece, color: Color) {
+        let bit = square.bb();
 
+        self.pieces[color as usize][piece as usize] |= bit;
+        self.mailbox[square as usize] = Some((piece, color));
         match color {
-            Color::White => {
-                self.white_occupied |= square_bit;
-
-                match piece {
-                    Piece::Pawn => self.white_pawns |= square_bit,
-                    Piece::Knight => self.white_knights |= square_bit,
-                    Piece::Bishop => self.white_bishops |= square_bit,
-                    Piece::Rook => self.white_rooks |= square_bit,
-                    Piece::Queen => self.white_queens |= square_bit,
-                    Piece::King => self.white_king |= square_bit,
-                }
-            }
-            Color::Black => {
-                self.black_occupied |= square_bit;
-
-                match piece {
-                    Piece::Pawn => self.black_pawns |= square_bit,
-                    Piece::Knight => self.black_knights |= square_bit,
-                    Piece::Bishop => self.black_bishops |= square_bit,
-                    Piece::Rook => self.black_rooks |= square_bit,
-                    Piece::Queen => self.black_queens |= square_bit,
-                    Piece::King => self.black_king |= square_bit,
-                }
-            }
+            Color::White => self.white_occupied |= bit,
+            Color::Black => self.black_occupied |= bit,
+        }
+        self.occupied |= bit;
+        self.empty &= !bit;
+    }
+
+    /// Lifts whatever stands on `square`, leaving the hash keys alone.
+    fn take(&mut self, square: Square) -> Option<(Piece, Color)> {
+        let (piece, color) = self.mailbox[square as usize].take()?;
+        let bit = square.bb();
+
+        self.pieces[color as usize][piece as usize] &= !bit;
+        match color {
+            Color::White => self.white_occupied &= !bit,
+            Color::Black => self.black_occupied &= !bit,
         }
+        self.occupied &= !bit;
+        self.empty |= bit;
 
-        self.occupied |= square_bit;
-        self.empty &= !square_bit;
+        Some((piece, color))
     }
 
-    pub fn delete_piece(&mut self, square: Square) {
-        let square_bit = square.bb();
-        let piece = self.piece_on_square(square);
-
-        if piece.is_some() {
-            let (piece, color) = piece.unwrap();
-
-            match color {
-                Color::White => {
-                    self.white_occupied ^= square_bit;
-
-                    match piece {
-                        Piece::Pawn => self.white_pawns ^= square_bit,
-                        Piece::Knight => self.white_knights ^= square_bit,
-                        Piece::Bishop => self.white_bishops ^= square_bit,
-                        Piece::Rook => self.white_rooks ^= square_bit,
-                        Piece::Queen => self.white_queens ^= square_bit,
-                        Piece::King => self.white_king ^= square_bit,
-                    }
-                }
-                Color::Black => {
-                    self.black_occupied ^= square_bit;
-
-                    match piece {
-                        Piece::Pawn => self.black_pawns ^= square_bit,
-                        Piece::Knight => self.black_knights ^= square_bit,
-                        Piece::Bishop => self.black_bishops ^= square_bit,
-                        Piece::Rook => self.black_rooks ^= square_bit,
-                        Piece::Queen => self.black_queens ^= square_bit,
-                        Piece::King => self.black_king ^= square_bit,
-                    }
-                }
-            }
+    /// Adds or removes a piece from the hash keys.
+    fn toggle_piece_key(&mut self, color: Color, piece: Piece, square: Square) {
+        let key = ZOBRIST.piece(color, piece, square);
 
-            self.occupied ^= square_bit;
-            self.empty |= square_bit;
+        self.zobrist_hash ^= key;
+        if piece == Piece::Pawn {
+            self.pawn_key ^= key;
         }
     }
 
     pub fn piece_on_square(&self, square: Square) -> Option<(Piece, Color)> {
-        let square_bit = square.bb();
-
-        if (self.white_occupied.0 & square_bit.0) != 0 {
-            if (self.white_pawns.0 & square_bit.0) != 0 {
-                Some((Piece::Pawn, Color::White))
-            } else if (self.white_knights.0 & square_bit.0) != 0 {
-                Some((Piece::Knight, Color::White))
-            } else if (self.white_bishops.0 & square_bit.0) != 0 {
-                Some((Piece::Bishop, Color::White))
-            } else if (self.white_rooks.0 & square_bit.0) != 0 {
-                Some((Piece::Rook, Color::White))
-            } else if (self.white_queens.0 & square_bit.0) != 0 {
-                Some((Piece::Queen, Color::White))
-            } else if (self.white_king.0 & square_bit.0) != 0 {
-                Some((Piece::King, Color::White))
-            } else {
-                None
-            } // Should not happen if white_occupied is correct
-        } else if (self.black_occupied.0 & square_bit.0) != 0 {
-            if (self.black_pawns.0 & square_bit.0) != 0 {
-                Some((Piece::Pawn, Color::Black))
-            } else if (self.black_knights.0 & square_bit.0) != 0 {
-                Some((Piece::Knight, Color::Black))
-            } else if (self.black_bishops.0 & square_bit.0) != 0 {
-                Some((Piece::Bishop, Color::Black))
-            } else if (self.black_rooks.0 & square_bit.0) != 0 {
-                Some((Piece::Rook, Color::Black))
-            } else if (self.black_queens.0 & square_bit.0) != 0 {
-                Some((Piece::Queen, Color::Black))
-            } else if (self.black_king.0 & square_bit.0) != 0 {
-                Some((Piece::King, Color::Black))
-            } else {
-                None
-            } // Should not happen if black_occupied is correct
-        } else {
-            None
-        }
+        self.mailbox[square as usize]
     }
 
     pub fn print(&self) {
@@ -668,47 +504,39 @@ impl Board {
             print!("| ");
             for file in 0..8 {
                 // Files A to H
-                let square_index = rank * 8 + file;
-                let square_bit = BitBoard(1u64 << square_index);
-
-                let piece_char = if (self.white_pawns.0 & square_bit.0) != 0 {
-                    'P'
-                } else if (self.white_knights.0 & square_bit.0) != 0 {
-                    'N'
-                } else if (self.white_bishops.0 & square_bit.0) != 0 {
-                    'B'
-                } else if (self.white_rooks.0 & square_bit.0) != 0 {
-                    'R'
-                } else if (self.white_queens.0 & square_bit.0) != 0 {
-                    'Q'
-                } else if (self.white_king.0 & square_bit.0) != 0 {
-                    'K'
-                } else if (self.black_pawns.0 & square_bit.0) != 0 {
-                    'p'
-                } else if (self.black_knights.0 & square_bit.0) != 0 {
-                    'n'
-                } else if (self.black_bishops.0 & square_bit.0) != 0 {
-                    'b'
-                } else if (self.black_rooks.0 & square_bit.0) != 0 {
-                    'r'
-                } else if (self.black_queens.0 & square_bit.0) != 0 {
-                    'q'
-                } else if (self.black_king.0 & square_bit.0) != 0 {
-                    'k'
-                } else {
-                    '.'
+                let square = Square::from_index(rank * 8 + file);
+
+                let piece_char = match self.piece_on_square(square) {
+                    Some((piece, Color::White)) => piece.to_char().to_ascii_uppercase(),
+                    Some((piece, Color::Black)) => piece.to_char(),
+                    None => '.',
                 };
                 print!("{} | ", piece_char);
             }
             println!();
             println!("+---+---+---+---+---+---+---+---+");
         }
+        println!("Fen: {}", self.to_fen());
         println!("Turn: {:?}", self.turn);
         println!("Castling Rights: {:b}", self.castling_rights);
         println!("En Passant Square: {:?}", self.en_passant_square);
         println!("Halfmove Clock: {}", self.halfmove_clock);
         println!("Fullmove Number: {}", self.fullmove_number);
         println!("Zobrist Hash: {}", self.zobrist_hash);
+
+        let squares = |bb: BitBoard| {
+            let names: Vec<String> = bb.into_iter().map(|sq| sq.to_string()).collect();
+            if names.is_empty() {
+                "-".to_string()
+            } else {
+                names.join(" ")
+            }
+        };
+        let them = self.turn.opposite();
+        let hanging =
+            self.occupied_by(self.turn) & self.attacks_by(them) & !self.attacks_by(self.turn);
+        println!("Checkers: {}", squares(self.checkers()));
+        println!("Hanging: {}", squares(hanging));
     }
 }
 
@@ -722,19 +550,55 @@ mod tests {
     fn test_default_board_setup() {
         let board = Board::default();
 
-        assert_eq!(board.white_pawns.0, 0x000000000000FF00);
-        assert_eq!(board.white_knights.0, 0x0000000000000042);
-        assert_eq!(board.white_bishops.0, 0x0000000000000024);
-        assert_eq!(board.white_rooks.0, 0x0000000000000081);
-        assert_eq!(board.white_queens.0, 0x0000000000000008);
-        assert_eq!(board.white_king.0, 0x0000000000000010);
+        assert_eq!(
+            board.pieces_of(Color::White, Piece::Pawn).0,
+            0x000000000000FF00
+        );
+        assert_eq!(
+            board.pieces_of(Color::White, Piece::Knight).0,
+            0x0000000000000042
+        );
+        assert_eq!(
+            board.pieces_of(Color::White, Piece::Bishop).0,
+            0x0000000000000024
+        );
+        assert_eq!(
+            board.pieces_of(Color::White, Piece::Rook).0,
+            0x0000000000000081
+        );
+        assert_eq!(
+            board.pieces_of(Color::White, Piece::Queen).0,
+            0x0000000000000008
+        );
+        assert_eq!(
+            board.pieces_of(Color::White, Piece::King).0,
+            0x0000000000000010
+        );
 
-        assert_eq!(board.black_pawns.0, 0x00FF000000000000);
-        assert_eq!(board.black_knights.0, 0x4200000000000000);
-        assert_eq!(board.black_bishops.0, 0x2400000000000000);
-        assert_eq!(board.black_rooks.0, 0x8100000000000000);
-        assert_eq!(board.black_queens.0, 0x0800000000000000);
-        assert_eq!(board.black_king.0, 0x1000000000000000);
+        assert_eq!(
+            board.pieces_of(Color::Black, Piece::Pawn).0,
+            0x00FF000000000000
+        );
+        assert_eq!(
+            board.pieces_of(Color::Black, Piece::Knight).0,
+            0x4200000000000000
+        );
+        assert_eq!(
+            board.pieces_of(Color::Black, Piece::Bishop).0,
+            0x2400000000000000
+        );
+        assert_eq!(
+            board.pieces_of(Color::Black, Piece::Rook).0,
+            0x8100000000000000
+        );
+        assert_eq!(
+            board.pieces_of(Color::Black, Piece::Queen).0,
+            0x0800000000000000
+        );
+        assert_eq!(
+            board.pieces_of(Color::Black, Piece::King).0,
+            0x1000000000000000
+        );
 
         assert_eq!(board.white_occupied.0, 0x000000000000FFFF);
         assert_eq!(board.black_occupied.0, 0xFFFF000000000000);
@@ -746,13 +610,13 @@ mod tests {
         assert_eq!(board.en_passant_square, None);
         assert_eq!(board.halfmove_clock, 0);
         assert_eq!(board.fullmove_number, 1);
-        assert_eq!(board.zobrist_hash, 0);
+        assert_eq!(board.zobrist_hash, board.compute_hash());
     }
 
     #[test]
     fn test_make_and_unmake_pawn_move() {
         let mut board = Board::default();
-        let initial_zobrist_hash = board.zobrist_hash; // Should be 0 for now
+        let initial_zobrist_hash = board.zobrist_hash;
 
         // Simulate e2e4 (White pawn from square 12 to 28)
         let mv = Move {
@@ -765,7 +629,7 @@ mod tests {
         };
 
         // Store pre-move state for unmake verification
-        let pre_move_white_pawns = board.white_pawns;
+        let pre_move_white_pawns = board.pieces_of(Color::White, Piece::Pawn);
         let pre_move_black_occupied = board.black_occupied;
         let pre_move_turn = board.turn;
         let pre_move_halfmove_clock = board.halfmove_clock;
@@ -775,12 +639,12 @@ mod tests {
 
         // Verify board state after move
         assert_eq!(
-            board.white_pawns.0 & (1u64 << 12),
+            board.pieces_of(Color::White, Piece::Pawn).0 & (1u64 << 12),
             0,
             "Pawn should be removed from E2"
         );
         assert_ne!(
-            board.white_pawns.0 & (1u64 << 28),
+            board.pieces_of(Color::White, Piece::Pawn).0 & (1u64 << 28),
             0,
             "Pawn should be on E4"
         );
@@ -799,7 +663,8 @@ mod tests {
 
         // Verify board state after unmake
         assert_eq!(
-            board.white_pawns, pre_move_white_pawns,
+            board.pieces_of(Color::White, Piece::Pawn),
+            pre_move_white_pawns,
             "White pawns should be restored"
         );
         assert_eq!(
@@ -810,9 +675,14 @@ mod tests {
             board.turn, pre_move_turn,
             "Turn should be restored to White"
         );
-        // Note: halfmove_clock and fullmove_number unmaking is incomplete in current `unmake_move`
-        // assert_eq!(board.halfmove_clock, pre_move_halfmove_clock, "Halfmove clock should be restored");
-        // assert_eq!(board.fullmove_number, pre_move_fullmove_number, "Fullmove number should be restored");
+        assert_eq!(
+            board.halfmove_clock, pre_move_halfmove_clock,
+            "Halfmove clock should be restored"
+        );
+        assert_eq!(
+            board.fullmove_number, pre_move_fullmove_number,
+            "Fullmove number should be restored"
+        );
         assert_eq!(
             board.zobrist_hash, initial_zobrist_hash,
             "Zobrist hash should be restored"
@@ -821,20 +691,11 @@ mod tests {
 
     #[test]
     fn test_make_and_unmake_capture() {
-        let mut board = Board::default();
         // Set up a simple capture scenario: White pawn on E2, Black pawn on D3
-        // This is a simplified direct manipulation for testing.
-        // In a real engine, you'd parse FEN or make moves.
-        board.white_pawns = BitBoard(1u64 << 12); // White pawn on E2
-        board.black_pawns = BitBoard(1u64 << 19); // Black pawn on D3 (square 19)
-        board.white_occupied = board.white_pawns;
-        board.black_occupied = board.black_pawns;
-        board.occupied = board.white_occupied | board.black_occupied;
-        board.empty = !board.occupied;
-        board.turn = Color::White;
-
-        let initial_white_pawns = board.white_pawns;
-        let initial_black_pawns = board.black_pawns;
+        let mut board = Board::from_fen("4k3/8/8/8/8/3p4/4P3/4K3 w - - 0 1").unwrap();
+
+        let initial_white_pawns = board.pieces_of(Color::White, Piece::Pawn);
+        let initial_black_pawns = board.pieces_of(Color::Black, Piece::Pawn);
         let initial_turn = board.turn;
 
         // White pawn captures Black pawn: e2xd3
@@ -843,25 +704,25 @@ mod tests {
             to: Square::D3,   // D3
             piece: Piece::Pawn,
             promotion: None,
-            flags: Flags::Normal, // No special flags for simple capture
-            captured_piece: None,
+            flags: Flags::Capture,
+            captured_piece: Some(Piece::Pawn),
         };
 
         board.make_move(&mv);
 
         // Verify state after capture
         assert_eq!(
-            board.white_pawns.0 & (1u64 << 12),
+            board.pieces_of(Color::White, Piece::Pawn).0 & (1u64 << 12),
             0,
             "White pawn removed from E2"
         );
         assert_ne!(
-            board.white_pawns.0 & (1u64 << 19),
+            board.pieces_of(Color::White, Piece::Pawn).0 & (1u64 << 19),
             0,
             "White pawn moved to D3"
         );
         assert_eq!(
-            board.black_pawns.0 & (1u64 << 19),
+            board.pieces_of(Color::Black, Piece::Pawn).0 & (1u64 << 19),
             0,
             "Black pawn removed from D3 (captured)"
         );
@@ -871,13 +732,53 @@ mod tests {
             "Halfmove clock should reset on capture"
         );
 
-        // Unmake the capture (note: current unmake_move doesn't restore captured pieces)
-        // This test will fail if `unmake_move` doesn't fully restore captured pieces.
-        // For a complete unmake, the `Move` struct would need to store `Option<Piece>` for captured piece.
-        // board.unmake_move(&mv);
-        // assert_eq!(board.white_pawns, initial_white_pawns, "White pawns should be restored after unmake");
-        // assert_eq!(board.black_pawns, initial_black_pawns, "Black pawns should be restored after unmake");
-        // assert_eq!(board.turn, initial_turn, "Turn should be restored after unmake");
+        board.unmake_move(&mv);
+        assert_eq!(
+            board.pieces_of(Color::White, Piece::Pawn),
+            initial_white_pawns,
+            "White pawns should be restored after unmake"
+        );
+        assert_eq!(
+            board.pieces_of(Color::Black, Piece::Pawn),
+            initial_black_pawns,
+            "Black pawns should be restored after unmake"
+        );
+        assert_eq!(
+            board.turn, initial_turn,
+            "Turn should be restored after unmake"
+        );
+    }
+
+    #[test]
+    fn test_make_and_unmake_en_passant() {
+        let mut board =
+            Board::from_fen("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3")
+                .unwrap();
+        let pre_move_white_pawns = board.pieces_of(Color::White, Piece::Pawn);
+        let pre_move_black_pawns = board.pieces_of(Color::Black, Piece::Pawn);
+
+        let mv = Move {
+            from: Square::E5,
+            to: Square::F6,
+            piece: Piece::Pawn,
+            promotion: None,
+            flags: Flags::EnPassant,
+            captured_piece: Some(Piece::Pawn),
+        };
+
+        board.make_move(&mv);
+        assert_eq!(board.piece_on_square(Square::F5), None);
+
+        board.unmake_move(&mv);
+        assert_eq!(
+            board.pieces_of(Color::White, Piece::Pawn),
+            pre_move_white_pawns
+        );
+        assert_eq!(
+            board.pieces_of(Color::Black, Piece::Pawn),
+            pre_move_black_pawns
+        );
+        assert_eq!(board.piece_on_square(Square::F6), None);
     }
 
     #[test]
@@ -915,4 +816,50 @@ mod tests {
         // Test empty square
         assert_eq!(board.piece_on_square(Square::E3), None); // E3 (empty)
     }
+
+    /// Checks the mailbox and the occupancy boards against the piece
+    /// bitboards.
+    fn assert_in_sync(board: &Board) {
+        for index in 0..64 {
+            let square = Square::from_index(index);
+            let expected = [Color::White, Color::Black].into_iter().find_map(|color| {
+                [
+                    Piece::Pawn,
+                    Piece::Knight,
+                    Piece::Bishop,
+                    Piece::Rook,
+                    Piece::Queen,
+                    Piece::King,
+                ]
+                .into_iter()
+                .find(|&piece| board.pieces_of(color, piece).has(square))
+                .map(|piece| (piece, color))
+            });
+
+            assert_eq!(board.piece_on_square(square), expected, "{:?}", square);
+            assert_eq!(board.occupied.has(square), expected.is_some());
+        }
+    }
+
+    #[test]
+    fn test_mailbox_follows_make_and_unmake() {
+        // Castling, en passant and captures all occur here.
+        let mut board =
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+
+        for mv in board.generate_legal_moves() {
+            board.make_move(&mv);
+            assert_in_sync(&board);
+
+            for reply in board.generate_legal_moves() {
+                board.make_move(&reply);
+                assert_in_sync(&board);
+                board.unmake_move(&reply);
+            }
+
+            board.unmake_move(&mv);
+            assert_in_sync(&board);
+        }
+    }
 }
@@ -1,10 +1,8 @@
+use std::fmt;
 use std::sync::OnceLock;
 
 use crate::bitboard::BitBoard;
 
-pub const KNIGHT_MOVES: [i8; 8] = [-17, -15, -10, -6, 6, 10, 15, 17];
-pub const KING_MOVES: [i8; 8] = [-9, -8, -7, -1, 1, 7, 8, 9];
-
 macro_rules! simple_enum {
     ($(
         pub enum $name:ident {
@@ -101,6 +99,26 @@ impl Square {
             Rank::try_index((self.rank() as i8 + rank_offset).try_into().ok()?)?,
         )
     }
+
+    /// Parses a square name such as `e4`.
+    pub fn from_name(name: &str) -> Option<Square> {
+        match name.as_bytes() {
+            &[file @ b'a'..=b'h', rank @ b'1'..=b'8'] => Square::new(
+                File::index((file - b'a') as usize),
+                Rank::index((rank - b'1') as usize),
+            ),
+            _ => None,
+        }
+    }
+}
+
+/// Writes the square name, e.g. `e4`.
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let file = (b'a' + self.file() as u8) as char;
+        let rank = (b'1' + self.rank() as u8) as char;
+        write!(f, "{}{}", file, rank)
+    }
 }
 
 pub struct Magic {
@@ -110,6 +128,8 @@ pub struct Magic {
     pub offset: usize
 }
 
+/// Used by the ray-walking reference generator in tests.
+#[cfg(test)]
 pub const DIRECTION_OFFSETS: [i32; 8] = [8, -8, -1, 1, 7, -7, 9, -9];
 
 
@@ -227,7 +247,7 @@ pub const KNIGHT_ATTACKS: [u64; 64] = [
     0x22140000000000,
     0x44280000000000,
     0x88500000000000,
-    0x10a000000000000,
+    0x10a00000000000,
     0x20400000000000,
 ];
 
@@ -402,4 +422,181 @@ const fn generate_king_attacks() -> [BitBoard; 64] {
     }
 
     attacks
-}
\ No newline at end of file
+}
+const fn init_file_masks() -> [u64; 8] {
+    let mut masks = [0u64; 8];
+    let mut file = 0;
+
+    while file < 8 {
+        masks[file] = 0x0101010101010101u64 << file;
+        file += 1;
+    }
+
+    masks
+}
+
+pub const FILE_MASKS: [u64; 8] = init_file_masks();
+
+const fn init_adjacent_file_masks() -> [u64; 8] {
+    let mut masks = [0u64; 8];
+    let mut file = 0;
+
+    while file < 8 {
+        if file > 0 {
+            masks[file] |= FILE_MASKS[file - 1];
+        }
+        if file < 7 {
+            masks[file] |= FILE_MASKS[file + 1];
+        }
+        file += 1;
+    }
+
+    masks
+}
+
+pub const ADJACENT_FILE_MASKS: [u64; 8] = init_adjacent_file_masks();
+
+/// Ranks strictly in front of a square from each side's point of view
+/// (index 0 White, 1 Black).
+const fn init_forward_rank_masks() -> [[u64; 64]; 2] {
+    let mut masks = [[0u64; 64]; 2];
+    let mut square = 0;
+
+    while square < 64 {
+        let rank = square / 8;
+        // Everything above this rank for White, everything below for Black.
+        masks[0][square] = if rank < 7 {
+            !0u64 << ((rank + 1) * 8)
+        } else {
+            0
+        };
+        masks[1][square] = if rank > 0 {
+            !0u64 >> ((8 - rank) * 8)
+        } else {
+            0
+        };
+        square += 1;
+    }
+
+    masks
+}
+
+const FORWARD_RANK_MASKS: [[u64; 64]; 2] = init_forward_rank_masks();
+
+const fn init_forward_file_masks() -> [[u64; 64]; 2] {
+    let mut masks = [[0u64; 64]; 2];
+    let mut square = 0;
+
+    while square < 64 {
+        let file = square % 8;
+        masks[0][square] = FORWARD_RANK_MASKS[0][square] & FILE_MASKS[file];
+        masks[1][square] = FORWARD_RANK_MASKS[1][square] & FILE_MASKS[file];
+        square += 1;
+    }
+
+    masks
+}
+
+/// Squares in front of a pawn on its own file.
+pub const FORWARD_FILE_MASKS: [[u64; 64]; 2] = init_forward_file_masks();
+
+const fn init_passed_pawn_masks() -> [[u64; 64]; 2] {
+    let mut masks = [[0u64; 64]; 2];
+    let mut square = 0;
+
+    while square < 64 {
+        let files = FILE_MASKS[square % 8] | ADJACENT_FILE_MASKS[square % 8];
+        masks[0][square] = FORWARD_RANK_MASKS[0][square] & files;
+        masks[1][square] = FORWARD_RANK_MASKS[1][square] & files;
+        square += 1;
+    }
+
+    masks
+}
+
+/// Squares in front of a pawn on its own and the adjacent files; the pawn is
+/// passed when no enemy pawn stands on any of them.
+pub const PASSED_PAWN_MASKS: [[u64; 64]; 2] = init_passed_pawn_masks();
+
+const QUEEN_DIRECTIONS: [(i8, i8); 8] = [
+    (0, 1),
+    (0, -1),
+    (1, 0),
+    (-1, 0),
+    (1, 1),
+    (-1, -1),
+    (1, -1),
+    (-1, 1),
+];
+
+const fn init_between_masks() -> [[u64; 64]; 64] {
+    let mut masks = [[0u64; 64]; 64];
+    let mut square = 0;
+
+    while square < 64 {
+        let mut dir = 0;
+        while dir < 8 {
+            let (df, dr) = QUEEN_DIRECTIONS[dir];
+            let mut file = (square % 8) as i8 + df;
+            let mut rank = (square / 8) as i8 + dr;
+            let mut passed = 0u64;
+
+            while file >= 0 && file < 8 && rank >= 0 && rank < 8 {
+                let target = (rank * 8 + file) as usize;
+                masks[square][target] = passed;
+                passed |= 1u64 << target;
+                file += df;
+                rank += dr;
+            }
+            dir += 1;
+        }
+        square += 1;
+    }
+
+    masks
+}
+
+/// Squares strictly between two squares on a shared rank, file or diagonal;
+/// empty when they are not aligned.
+pub static BETWEEN_MASKS: [[u64; 64]; 64] = init_between_masks();
+
+const fn init_line_masks() -> [[u64; 64]; 64] {
+    let mut masks = [[0u64; 64]; 64];
+    let mut square = 0;
+
+    while square < 64 {
+        // Directions come in opposite pairs, so each pair is one line.
+        let mut dir = 0;
+        while dir < 8 {
+            let mut line = 1u64 << square;
+            let mut half = 0;
+            while half < 2 {
+                let (df, dr) = QUEEN_DIRECTIONS[dir + half];
+                let mut file = (square % 8) as i8 + df;
+                let mut rank = (square / 8) as i8 + dr;
+                while file >= 0 && file < 8 && rank >= 0 && rank < 8 {
+                    line |= 1u64 << (rank * 8 + file);
+                    file += df;
+                    rank += dr;
+                }
+                half += 1;
+            }
+
+            let mut target = 0;
+            while target < 64 {
+                if target != square && line & (1u64 << target) != 0 {
+                    masks[square][target] = line;
+                }
+                target += 1;
+            }
+            dir += 2;
+        }
+        square += 1;
+    }
+
+    masks
+}
+
+/// The whole rank, file or diagonal through two aligned squares, edge to
+/// edge; empty when they are not aligned.
+pub static LINE_MASKS: [[u64; 64]; 64] = init_line_masks();
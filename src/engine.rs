@@ -0,0 +1,1080 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{
+    board::{Board, Color, Piece},
+    consts::Rank,
+    evaluation::Evaluator,
+    movepick::{mvv_lva, MoveHistory, MovePicker},
+    moves::{Move, MoveList},
+    tt::{Bound, TranspositionTable, DEFAULT_HASH_MB},
+};
+
+pub const INFINITY: i32 = 32_000;
+pub const MATE: i32 = 31_000;
+pub const MAX_PLY: usize = 128;
+/// Any score at or beyond this bound is a forced mate.
+pub const MATE_BOUND: i32 = MATE - MAX_PLY as i32;
+
+const PAWN_VALUE: i32 = 100;
+const KNIGHT_VALUE: i32 = 320;
+const BISHOP_VALUE: i32 = 330;
+const ROOK_VALUE: i32 = 500;
+const QUEEN_VALUE: i32 = 900;
+
+/// How often (in nodes) the search polls the clock and the stop flag.
+const CHECK_INTERVAL: u64 = 2048;
+
+const RFP_MAX_DEPTH: u32 = 6;
+/// Reverse futility margin per ply of remaining depth.
+const RFP_MARGIN: i32 = 80;
+const NMP_MIN_DEPTH: u32 = 3;
+/// Null move cutoffs from this depth on are verified when enabled.
+const NMP_VERIFY_DEPTH: u32 = 6;
+const FUTILITY_MAX_DEPTH: u32 = 3;
+/// Futility margin per ply of remaining depth.
+const FUTILITY_MARGIN: i32 = 150;
+const LMP_MAX_DEPTH: u32 = 4;
+/// Quiet moves searched before late move pruning starts, plus depth squared.
+const LMP_BASE: u32 = 3;
+const LMR_MIN_DEPTH: u32 = 3;
+/// Moves searched at full depth before reductions start.
+const LMR_MIN_MOVES: usize = 3;
+const SE_MIN_DEPTH: u32 = 7;
+
+/// Constraints for a single search, mirroring the arguments of UCI `go`.
+/// Times are in milliseconds.
+#[derive(Debug, Default, Clone)]
+pub struct SearchLimits {
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: Option<u64>,
+    pub binc: Option<u64>,
+    pub movestogo: Option<u32>,
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<u64>,
+    pub infinite: bool,
+    pub ponder: bool,
+}
+
+/// Search features that can be switched on and off, e.g. from UCI options.
+#[derive(Debug, Clone)]
+pub struct SearchParams {
+    /// Also try quiet checking moves on the first ply of quiescence search.
+    pub qsearch_checks: bool,
+    pub null_move: bool,
+    /// Re-search null move cutoffs at high depth without null moves.
+    pub null_move_verification: bool,
+    pub late_move_reductions: bool,
+    pub reverse_futility: bool,
+    pub futility: bool,
+    pub late_move_pruning: bool,
+    /// Search moves after the first with a null window first.
+    pub pvs: bool,
+    pub check_extension: bool,
+    /// Extend the hash move when every other move fails low against it.
+    pub singular_extension: bool,
+    /// Extend passed pawn pushes to the seventh rank.
+    pub passed_pawn_extension: bool,
+    pub mate_distance_pruning: bool,
+}
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        SearchParams {
+            qsearch_checks: false,
+            null_move: true,
+            null_move_verification: false,
+            late_move_reductions: true,
+            reverse_futility: true,
+            futility: true,
+            late_move_pruning: true,
+            pvs: true,
+            check_extension: true,
+            singular_extension: true,
+            passed_pawn_extension: true,
+            mate_distance_pruning: true,
+        }
+    }
+}
+
+/// Progress report emitted after every completed iteration.
+#[derive(Debug, Clone)]
+pub struct SearchInfo {
+    pub depth: u32,
+    pub score: i32,
+    pub nodes: u64,
+    pub time: Duration,
+    /// Transposition table fill in permille.
+    pub hashfull: u32,
+    pub pv: Vec<Move>,
+}
+
+pub type InfoCallback = Box<dyn FnMut(&SearchInfo) + Send>;
+
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    /// Best move of the deepest completed iteration. Only `None` when the
+    /// root position has no legal moves.
+    pub best_move: Option<Move>,
+    pub score: i32,
+    pub depth: u32,
+    pub nodes: u64,
+    pub pv: Vec<Move>,
+}
+
+pub struct Engine {
+    pub params: SearchParams,
+    pub evaluator: Evaluator,
+    pub tt: TranspositionTable,
+    stop: Arc<AtomicBool>,
+    /// Set once this search hit a limit or saw `stop`; unlike `stop` it is
+    /// owned by the search and reset every time it starts.
+    aborted: bool,
+    pondering: Arc<AtomicBool>,
+    nodes: u64,
+    start: Instant,
+    soft_deadline: Option<Instant>,
+    hard_deadline: Option<Instant>,
+    /// Set while the deadlines still count from the start of a `go ponder`
+    /// search instead of from `ponderhit`.
+    awaiting_ponderhit: bool,
+    node_limit: Option<u64>,
+    /// Length of the board history at the root, which separates the game's
+    /// positions from the ones reached in the search.
+    root_len: usize,
+    /// Triangular PV table: `pv[ply]` holds the best line found from `ply`.
+    pv: Vec<Vec<Move>>,
+    pub history: MoveHistory,
+    /// The move being searched at each ply, for countermoves; `None` for a
+    /// null move.
+    played: [Option<Move>; MAX_PLY + 1],
+    /// Set during a null move verification search, which may not pass.
+    verifying: bool,
+    /// The move left out at each ply by a singular extension search.
+    excluded: [Option<Move>; MAX_PLY + 1],
+    /// Late move reductions by depth and number of moves searched.
+    reductions: [[u8; 64]; 64],
+    on_info: Option<InfoCallback>,
+}
+
+impl Engine {
+    pub fn new() -> Engine {
+        Engine {
+            params: SearchParams::default(),
+            evaluator: Evaluator::default(),
+            tt: TranspositionTable::new(DEFAULT_HASH_MB),
+            stop: Arc::new(AtomicBool::new(false)),
+            aborted: false,
+            pondering: Arc::new(AtomicBool::new(false)),
+            nodes: 0,
+            start: Instant::now(),
+            soft_deadline: None,
+            hard_deadline: None,
+            awaiting_ponderhit: false,
+            node_limit: None,
+            root_len: 0,
+            pv: (0..=MAX_PLY).map(|_| Vec::with_capacity(MAX_PLY)).collect(),
+            history: MoveHistory::default(),
+            played: [None; MAX_PLY + 1],
+            verifying: false,
+            excluded: [None; MAX_PLY + 1],
+            reductions: reduction_table(),
+            on_info: None,
+        }
+    }
+
+    /// Flag that aborts a running search as soon as it is set.
+    pub fn stop_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop)
+    }
+
+    /// Flag that suspends time limits while it is set (UCI `go ponder`).
+    pub fn ponder_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.pondering)
+    }
+
+    pub fn set_info_callback(&mut self, callback: impl FnMut(&SearchInfo) + Send + 'static) {
+        self.on_info = Some(Box::new(callback));
+    }
+
+    /// Runs an iterative deepening search from `board`. The board is only
+    /// modified through `make_move`/`unmake_move` and is restored on return.
+    pub fn search(&mut self, board: &mut Board, limits: &SearchLimits) -> SearchResult {
+        self.nodes = 0;
+        self.aborted = false;
+        self.start = Instant::now();
+        self.node_limit = limits.nodes;
+        self.root_len = board.history.len();
+        self.set_deadlines(board.turn, limits);
+        self.awaiting_ponderhit = self.pondering.load(Ordering::Relaxed);
+        self.tt.new_search();
+        self.history.new_search();
+
+        let mut root_moves = MoveList::new();
+        board.generate_legal_moves_into(&mut root_moves);
+
+        let mut result = SearchResult {
+            best_move: root_moves.first().copied(),
+            score: 0,
+            depth: 0,
+            nodes: 0,
+            pv: Vec::new(),
+        };
+
+        if root_moves.is_empty() {
+            return result;
+        }
+
+        let max_depth = limits
+            .depth
+            .unwrap_or(MAX_PLY as u32 - 1)
+            .min(MAX_PLY as u32 - 1);
+
+        for depth in 1..=max_depth {
+            let score = self.negamax(board, depth, -INFINITY, INFINITY, 0, result.best_move);
+
+            if self.aborted {
+                break;
+            }
+
+            result.score = score;
+            result.depth = depth;
+            result.nodes = self.nodes;
+            result.pv = self.pv[0].clone();
+            result.best_move = result.pv.first().copied().or(result.best_move);
+
+            if let Some(on_info) = self.on_info.as_mut() {
+                on_info(&SearchInfo {
+                    depth,
+                    score,
+                    nodes: self.nodes,
+                    time: self.start.elapsed(),
+                    hashfull: self.tt.hashfull(),
+                    pv: result.pv.clone(),
+                });
+            }
+
+            // A found mate cannot get any shorter by searching deeper.
+            if score.abs() >= MATE_BOUND && (MATE - score.abs()) as u32 <= depth {
+                break;
+            }
+
+            if self.past_soft_deadline() {
+                break;
+            }
+        }
+
+        result.nodes = self.nodes;
+        result
+    }
+
+    fn negamax(
+        &mut self,
+        board: &mut Board,
+        depth: u32,
+        mut alpha: i32,
+        mut beta: i32,
+        ply: usize,
+        mut first: Option<Move>,
+    ) -> i32 {
+        if depth == 0 {
+            return self.quiescence(board, alpha, beta, ply, 0);
+        }
+
+        self.pv[ply].clear();
+
+        if self.should_stop() {
+            self.aborted = true;
+        }
+        if self.aborted {
+            return 0;
+        }
+
+        self.nodes += 1;
+
+        if ply >= MAX_PLY {
+            return self.evaluator.evaluate(board);
+        }
+
+        if ply > 0 && (board.is_repetition(self.root_len) || board.is_insufficient_material()) {
+            return 0;
+        }
+
+        let pv_node = beta - alpha > 1;
+
+        // Mate distance pruning: no line from here can beat a mate already
+        // found closer to the root.
+        if self.params.mate_distance_pruning && ply > 0 {
+            alpha = alpha.max(-MATE + ply as i32);
+            beta = beta.min(MATE - ply as i32 - 1);
+            if alpha >= beta {
+                return alpha;
+            }
+        }
+
+        // Set while this node is searched again without its hash move to see
+        // whether that move is singular.
+        let excluded = self.excluded[ply];
+        let tt_hit = self.tt.probe(board.zobrist_hash, ply);
+
+        if let Some(hit) = tt_hit {
+            let usable = match hit.bound {
+                Bound::Exact => true,
+                Bound::Lower => hit.score >= beta,
+                Bound::Upper => hit.score <= alpha,
+            };
+
+            // The root always searches so it has a move and a PV to report.
+            if ply > 0 && excluded.is_none() && hit.depth >= depth && usable {
+                return hit.score;
+            }
+        }
+
+        let in_check = board.is_king_in_check(board.turn.opposite());
+        let static_eval = if in_check {
+            -INFINITY
+        } else {
+            self.evaluator.evaluate(board)
+        };
+
+        // Reverse futility: far enough above beta that a shallow search is
+        // not going to bring the score back down.
+        if self.params.reverse_futility
+            && !pv_node
+            && !in_check
+            && excluded.is_none()
+            && depth <= RFP_MAX_DEPTH
+            && beta.abs() < MATE_BOUND
+            && static_eval - RFP_MARGIN * depth as i32 >= beta
+        {
+            return static_eval;
+        }
+
+        // Null move: if passing still fails high, a real move will too. Not
+        // twice in a row, and not with only pawns left, where passing may be
+        // the best move there is (zugzwang).
+        let prev = ply.checked_sub(1).and_then(|prev| self.played[prev]);
+        if self.params.null_move
+            && !pv_node
+            && !in_check
+            && !self.verifying
+            && excluded.is_none()
+            && prev.is_some()
+            && depth >= NMP_MIN_DEPTH
+            && static_eval >= beta
+            && has_non_pawn_material(board, board.turn)
+        {
+            let reduction = 3 + depth / 4 + ((static_eval - beta) / 200).min(3) as u32;
+
+            self.played[ply] = None;
+            board.make_null_move();
+            let score = -self.negamax(
+                board,
+                depth.saturating_sub(reduction),
+                -beta,
+                -beta + 1,
+                ply + 1,
+                None,
+            );
+            board.unmake_null_move();
+
+            if self.aborted {
+                return 0;
+            }
+
+            if score >= beta {
+                // A mate found after passing proves nothing.
+                let score = score.min(MATE_BOUND - 1);
+
+                if !self.params.null_move_verification || depth < NMP_VERIFY_DEPTH {
+                    return score;
+                }
+
+                // Verify at reduced depth without null moves, which catches
+                // the zugzwangs the material guard lets through.
+                self.verifying = true;
+                let verified = self.negamax(
+                    board,
+                    depth.saturating_sub(reduction),
+                    beta - 1,
+                    beta,
+                    ply,
+                    None,
+                );
+                self.verifying = false;
+
+                if verified >= beta {
+                    return score;
+                }
+            }
+        }
+
+        let tt_move = tt_hit.and_then(|hit| hit.best_move(board));
+        if first.is_none() {
+            first = tt_move;
+        }
+
+        let mut picker = MovePicker::new(first, self.history.refutations(ply, prev));
+        let mut quiets_tried = MoveList::new();
+        let mut moves_searched = 0;
+
+        let original_alpha = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+
+        while let Some(mv) = picker.next(board, &self.history) {
+            if Some(mv) == excluded {
+                continue;
+            }
+
+            let quiet = mv.is_quiet();
+            // Pruning only sets in once a move kept us from being mated.
+            let can_prune = !pv_node && !in_check && quiet && best_score > -MATE_BOUND;
+
+            // Late move pruning: at low depth, quiet moves this far down the
+            // ordering rarely do anything.
+            if self.params.late_move_pruning
+                && can_prune
+                && depth <= LMP_MAX_DEPTH
+                && quiets_tried.len() >= (LMP_BASE + depth * depth) as usize
+            {
+                continue;
+            }
+
+            // Singular extension: the hash move is worth an extra ply when
+            // every other move falls clearly short of its score.
+            let mut singular = false;
+            if let Some(hit) = tt_hit.filter(|_| {
+                self.params.singular_extension
+                    && ply > 0
+                    && excluded.is_none()
+                    && Some(mv) == tt_move
+            }) {
+                if depth >= SE_MIN_DEPTH
+                    && hit.bound != Bound::Upper
+                    && hit.depth + 3 >= depth
+                    && hit.score.abs() < MATE_BOUND
+                {
+                    let singular_beta = hit.score - 2 * depth as i32;
+
+                    self.excluded[ply] = Some(mv);
+                    let score = self.negamax(
+                        board,
+                        (depth - 1) / 2,
+                        singular_beta - 1,
+                        singular_beta,
+                        ply,
+                        None,
+                    );
+                    self.excluded[ply] = None;
+                    self.pv[ply].clear();
+
+                    if self.aborted {
+                        break;
+                    }
+                    singular = score < singular_beta;
+                }
+            }
+
+            let passed_push =
+                self.params.passed_pawn_extension && is_passed_push_to_seventh(board, &mv);
+
+            self.played[ply] = Some(mv);
+            board.make_move(&mv);
+            let gives_check = board.is_king_in_check(board.turn.opposite());
+
+            let extension =
+                (singular || passed_push || (self.params.check_extension && gives_check)) as u32;
+            let new_depth = depth - 1 + extension;
+
+            // Futility: a quiet move will not lift a static evaluation this
+            // far below alpha within the remaining depth.
+            if self.params.futility
+                && can_prune
+                && !gives_check
+                && depth <= FUTILITY_MAX_DEPTH
+                && static_eval + FUTILITY_MARGIN * depth as i32 <= alpha
+            {
+                board.unmake_move(&mv);
+                continue;
+            }
+
+            // Late move reductions: quiet moves late in the ordering get a
+            // shallower search first, and a full one only if they beat alpha.
+            let reduction = if self.params.late_move_reductions
+                && depth >= LMR_MIN_DEPTH
+                && moves_searched >= LMR_MIN_MOVES
+                && quiet
+                && !in_check
+                && !gives_check
+                && extension == 0
+            {
+                let r = self.reductions[(depth as usize).min(63)][moves_searched.min(63)] as u32;
+                r.saturating_sub(pv_node as u32).min(depth - 2)
+            } else {
+                0
+            };
+
+            let score = if moves_searched == 0 {
+                -self.negamax(board, new_depth, -beta, -alpha, ply + 1, None)
+            } else if self.params.pvs {
+                // Principal variation search: after the first move, only
+                // prove the others are no better, which a null window does
+                // cheaply, and search again in full when one is.
+                let mut score = -self.negamax(
+                    board,
+                    new_depth - reduction,
+                    -alpha - 1,
+                    -alpha,
+                    ply + 1,
+                    None,
+                );
+                if score > alpha && reduction > 0 {
+                    score = -self.negamax(board, new_depth, -alpha - 1, -alpha, ply + 1, None);
+                }
+                if score > alpha && score < beta {
+                    score = -self.negamax(board, new_depth, -beta, -alpha, ply + 1, None);
+                }
+                score
+            } else {
+                let mut score = INFINITY;
+                if reduction > 0 {
+                    score = -self.negamax(
+                        board,
+                        new_depth - reduction,
+                        -alpha - 1,
+                        -alpha,
+                        ply + 1,
+                        None,
+                    );
+                }
+                if score > alpha {
+                    score = -self.negamax(board, new_depth, -beta, -alpha, ply + 1, None);
+                }
+                score
+            };
+            board.unmake_move(&mv);
+            moves_searched += 1;
+
+            if self.aborted {
+                break;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_move = Some(mv);
+            }
+
+            if score > alpha {
+                alpha = score;
+
+                let (head, tail) = self.pv.split_at_mut(ply + 1);
+                head[ply].clear();
+                head[ply].push(mv);
+                head[ply].extend_from_slice(&tail[0]);
+            }
+
+            if alpha >= beta {
+                if quiet {
+                    self.history
+                        .record_cutoff(board.turn, &mv, &quiets_tried, ply, prev, depth);
+                }
+                break;
+            }
+
+            if quiet {
+                quiets_tried.push(mv);
+            }
+        }
+
+        if best_move.is_none() && !self.aborted {
+            // Only the excluded move was left, so this fails low.
+            if excluded.is_some() {
+                return alpha;
+            }
+
+            return if in_check { -MATE + ply as i32 } else { 0 };
+        }
+
+        if !self.aborted && excluded.is_none() {
+            let bound = if best_score >= beta {
+                Bound::Lower
+            } else if best_score > original_alpha {
+                Bound::Exact
+            } else {
+                Bound::Upper
+            };
+
+            // A fail-low gives no reason to prefer any move.
+            let best_move = best_move.filter(|_| bound != Bound::Upper);
+            self.tt
+                .store(board.zobrist_hash, depth, bound, best_score, best_move, ply);
+        }
+
+        best_score
+    }
+
+    /// Resolves pending tactics at the leaves so the static evaluation is only
+    /// trusted in quiet positions. `qply` counts plies since the main search.
+    fn quiescence(
+        &mut self,
+        board: &mut Board,
+        mut alpha: i32,
+        beta: i32,
+        ply: usize,
+        qply: u32,
+    ) -> i32 {
+        self.pv[ply].clear();
+
+        if self.should_stop() {
+            self.aborted = true;
+        }
+        if self.aborted {
+            return 0;
+        }
+
+        self.nodes += 1;
+
+        if ply >= MAX_PLY {
+            return self.evaluator.evaluate(board);
+        }
+
+        // Standing pat is only sound when not in check; in check every
+        // evasion has to be searched instead.
+        let in_check = board.is_king_in_check(board.turn.opposite());
+        let mut best_score = -INFINITY;
+
+        let mut moves = MoveList::new();
+
+        if in_check {
+            board.generate_legal_moves_into(&mut moves);
+
+            if moves.is_empty() {
+                return -MATE + ply as i32;
+            }
+        } else {
+            let stand_pat = self.evaluator.evaluate(board);
+
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+
+            alpha = alpha.max(stand_pat);
+            best_score = stand_pat;
+
+            let include_checks = self.params.qsearch_checks && qply == 0;
+            board.generate_tactical_moves_into(&mut moves, include_checks);
+            moves.sort_by_key(|mv| -mvv_lva(mv));
+        }
+
+        for mv in moves.iter() {
+            // Captures that lose material once the exchange is played out
+            // are not worth a search.
+            if !in_check && !board.see_ge(mv, 0) {
+                continue;
+            }
+
+            board.make_move(mv);
+            let score = -self.quiescence(board, -beta, -alpha, ply + 1, qply + 1);
+            board.unmake_move(mv);
+
+            if self.aborted {
+                break;
+            }
+
+            if score > best_score {
+                best_score = score;
+            }
+
+            if score > alpha {
+                alpha = score;
+
+                let (head, tail) = self.pv.split_at_mut(ply + 1);
+                head[ply].clear();
+                head[ply].push(*mv);
+                head[ply].extend_from_slice(&tail[0]);
+            }
+
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        best_score
+    }
+
+    fn set_deadlines(&mut self, turn: Color, limits: &SearchLimits) {
+        self.soft_deadline = None;
+        self.hard_deadline = None;
+
+        if limits.infinite {
+            return;
+        }
+
+        if let Some(movetime) = limits.movetime {
+            let deadline = self.start + Duration::from_millis(movetime);
+            self.soft_deadline = Some(deadline);
+            self.hard_deadline = Some(deadline);
+            return;
+        }
+
+        let (time, inc) = match turn {
+            Color::White => (limits.wtime, limits.winc.unwrap_or(0)),
+            Color::Black => (limits.btime, limits.binc.unwrap_or(0)),
+        };
+
+        if let Some(time) = time {
+            let moves_left = limits.movestogo.unwrap_or(30).max(1) as u64;
+            // Leave some slack for the GUI and never plan to use more than
+            // the clock actually has.
+            let safe_time = time.saturating_sub(50);
+            let soft = (time / moves_left + inc * 3 / 4).min(safe_time);
+            let hard = (soft * 4).min(safe_time / 2).max(soft);
+
+            self.soft_deadline = Some(self.start + Duration::from_millis(soft));
+            self.hard_deadline = Some(self.start + Duration::from_millis(hard));
+        }
+    }
+
+    fn should_stop(&mut self) -> bool {
+        if self.node_limit.is_some_and(|limit| self.nodes >= limit) {
+            return true;
+        }
+
+        if !self.nodes.is_multiple_of(CHECK_INTERVAL) {
+            return false;
+        }
+
+        if self.stop.load(Ordering::Relaxed) {
+            return true;
+        }
+
+        if !self.on_clock() {
+            return false;
+        }
+
+        matches!(self.hard_deadline, Some(deadline) if Instant::now() >= deadline)
+    }
+
+    fn past_soft_deadline(&mut self) -> bool {
+        if !self.on_clock() {
+            return false;
+        }
+
+        matches!(self.soft_deadline, Some(deadline) if Instant::now() >= deadline)
+    }
+
+    /// Whether the deadlines apply, which they do not while pondering. The
+    /// first call after `ponderhit` moves them on by the time spent
+    /// pondering, so they count from `ponderhit` instead.
+    fn on_clock(&mut self) -> bool {
+        if self.pondering.load(Ordering::Relaxed) {
+            return false;
+        }
+
+        if self.awaiting_ponderhit {
+            self.awaiting_ponderhit = false;
+            let pondered = self.start.elapsed();
+            self.soft_deadline = self.soft_deadline.map(|deadline| deadline + pondered);
+            self.hard_deadline = self.hard_deadline.map(|deadline| deadline + pondered);
+        }
+
+        true
+    }
+}
+
+fn reduction_table() -> [[u8; 64]; 64] {
+    let mut table = [[0; 64]; 64];
+
+    for (depth, row) in table.iter_mut().enumerate().skip(1) {
+        for (moves, reduction) in row.iter_mut().enumerate().skip(1) {
+            *reduction = (0.75 + (depth as f64).ln() * (moves as f64).ln() / 2.25) as u8;
+        }
+    }
+
+    table
+}
+
+/// A pawn on the seventh rank is always passed: no enemy pawn can stand in
+/// front of it.
+fn is_passed_push_to_seventh(board: &Board, mv: &Move) -> bool {
+    let seventh = match board.turn {
+        Color::White => Rank::Seventh,
+        Color::Black => Rank::Second,
+    };
+
+    mv.piece == Piece::Pawn && mv.to.rank() == seventh
+}
+
+/// Whether `color` has anything besides king and pawns. Without it, zugzwang
+/// is common enough that passing cannot be trusted.
+fn has_non_pawn_material(board: &Board, color: Color) -> bool {
+    [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen]
+        .into_iter()
+        .any(|piece| !board.pieces_of(color, piece).is_empty())
+}
+
+pub fn piece_value(piece: Piece) -> i32 {
+    match piece {
+        Piece::Pawn => PAWN_VALUE,
+        Piece::Knight => KNIGHT_VALUE,
+        Piece::Bishop => BISHOP_VALUE,
+        Piece::Rook => ROOK_VALUE,
+        Piece::Queen => QUEEN_VALUE,
+        Piece::King => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::consts::Square;
+
+    use super::*;
+
+    fn search_depth(fen: &str, depth: u32) -> (Board, SearchResult) {
+        let mut board = Board::from_fen(fen).unwrap();
+        let mut engine = Engine::new();
+        let limits = SearchLimits {
+            depth: Some(depth),
+            ..SearchLimits::default()
+        };
+        let result = engine.search(&mut board, &limits);
+        (board, result)
+    }
+
+    #[test]
+    fn test_finds_mate_in_one() {
+        let (_, result) = search_depth("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1", 3);
+
+        let best = result.best_move.unwrap();
+        assert_eq!(best.from, Square::A1);
+        assert_eq!(best.to, Square::A8);
+        assert_eq!(result.score, MATE - 1);
+    }
+
+    #[test]
+    fn test_captures_hanging_queen() {
+        let (_, result) = search_depth("4k3/8/8/3q4/8/8/8/3R1K2 w - - 0 1", 2);
+
+        let best = result.best_move.unwrap();
+        assert_eq!(best.from, Square::D1);
+        assert_eq!(best.to, Square::D5);
+    }
+
+    #[test]
+    fn test_search_restores_board() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let (board, _) = search_depth(fen, 3);
+        let fresh = Board::from_fen(fen).unwrap();
+
+        assert_eq!(board.occupied, fresh.occupied);
+        assert_eq!(board.white_occupied, fresh.white_occupied);
+        assert_eq!(board.castling_rights, fresh.castling_rights);
+        assert_eq!(board.turn, fresh.turn);
+        assert!(board.history.is_empty());
+    }
+
+    #[test]
+    fn test_best_move_available_when_stopped_early() {
+        let mut board = Board::default();
+        let mut engine = Engine::new();
+        let limits = SearchLimits {
+            nodes: Some(1),
+            ..SearchLimits::default()
+        };
+
+        let result = engine.search(&mut board, &limits);
+
+        assert!(result.best_move.is_some());
+    }
+
+    #[test]
+    fn test_deadlines_count_from_ponderhit() {
+        let mut engine = Engine::new();
+        let limits = SearchLimits {
+            ponder: true,
+            movetime: Some(1000),
+            ..SearchLimits::default()
+        };
+
+        // A `go ponder` search that started ten seconds ago.
+        engine.pondering.store(true, Ordering::Relaxed);
+        engine.start = Instant::now() - Duration::from_secs(10);
+        engine.set_deadlines(Color::White, &limits);
+        engine.awaiting_ponderhit = true;
+        assert!(!engine.past_soft_deadline());
+
+        engine.pondering.store(false, Ordering::Relaxed);
+        let ponderhit = Instant::now();
+        assert!(!engine.past_soft_deadline());
+        assert!(engine.hard_deadline.unwrap() >= ponderhit + Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn test_plays_for_mate_with_queen_or_rook() {
+        for fen in [
+            "4k3/8/8/8/8/8/8/3QK3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/3RK3 w - - 0 1",
+        ] {
+            let (_, result) = search_depth(fen, 6);
+
+            assert!(result.score >= ROOK_VALUE, "{}: {}", fen, result.score);
+        }
+    }
+
+    #[test]
+    fn test_game_repetition_is_not_a_draw() {
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+        for uci in ["e1f1", "e8f8", "f1e1"] {
+            let mv = board.parse_uci_move(uci).unwrap();
+            board.make_move(&mv);
+        }
+
+        // Kf8-e8 brings back the start position for the second time only,
+        // which the lost side may not take as a draw.
+        let mut engine = Engine::new();
+        let limits = SearchLimits {
+            depth: Some(4),
+            ..SearchLimits::default()
+        };
+        let result = engine.search(&mut board, &limits);
+
+        assert!(result.score <= -ROOK_VALUE, "{}", result.score);
+    }
+
+    #[test]
+    fn test_quiescence_sees_recapture() {
+        // Qxd5 wins a pawn at depth 1 unless the recapture cxd5 is resolved.
+        let (_, result) = search_depth("4k3/8/2p5/3p4/8/8/8/3Q1K2 w - - 0 1", 1);
+
+        let best = result.best_move.unwrap();
+        assert!(
+            best.to != Square::D5,
+            "queen should not take a defended pawn"
+        );
+    }
+
+    #[test]
+    fn test_quiescence_checks_are_optional() {
+        let mut board = Board::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        let mut engine = Engine::new();
+
+        let quiet = engine.quiescence(&mut board, -INFINITY, INFINITY, 0, 0);
+        assert_eq!(quiet, engine.evaluator.evaluate(&board));
+
+        engine.params.qsearch_checks = true;
+        let with_checks = engine.quiescence(&mut board, -INFINITY, INFINITY, 0, 0);
+        assert_eq!(with_checks, MATE - 1);
+    }
+
+    #[test]
+    fn test_no_move_when_checkmated() {
+        let (_, result) = search_depth("R5k1/5ppp/8/8/8/8/5PPP/6K1 b - - 0 1", 2);
+
+        assert!(result.best_move.is_none());
+    }
+
+    #[test]
+    fn test_pruning_keeps_forced_mate() {
+        // Ra6 mates next move whatever Black does, since Black is in
+        // zugzwang. Plain null move pruning lets Black pass and misses it;
+        // verification finds it again.
+        let fen = "kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1";
+        let all_off = SearchParams {
+            null_move: false,
+            late_move_reductions: false,
+            reverse_futility: false,
+            futility: false,
+            late_move_pruning: false,
+            pvs: false,
+            check_extension: false,
+            singular_extension: false,
+            passed_pawn_extension: false,
+            mate_distance_pruning: false,
+            ..SearchParams::default()
+        };
+        let no_null_move = SearchParams {
+            null_move: false,
+            ..SearchParams::default()
+        };
+        let verified = SearchParams {
+            null_move_verification: true,
+            ..SearchParams::default()
+        };
+
+        for (params, depth) in [(all_off, 6), (no_null_move, 6), (verified, 8)] {
+            let mut board = Board::from_fen(fen).unwrap();
+            let mut engine = Engine::new();
+            engine.params = params.clone();
+            let limits = SearchLimits {
+                depth: Some(depth),
+                ..SearchLimits::default()
+            };
+            let result = engine.search(&mut board, &limits);
+
+            assert_eq!(result.score, MATE - 3, "{:?}", params);
+            assert_eq!(result.best_move.unwrap().to, Square::A6, "{:?}", params);
+        }
+    }
+
+    #[test]
+    fn test_mate_distance_pruning_keeps_shortest_mate() {
+        // Qh8 mates at once; longer mates must not replace it.
+        let (_, result) = search_depth("k7/8/1K6/8/8/8/p7/7Q w - - 0 1", 5);
+
+        assert_eq!(result.score, MATE - 1);
+    }
+
+    #[test]
+    fn test_passed_push_to_seventh() {
+        let board = Board::from_fen("4k3/8/1P6/7P/8/8/8/4K3 w - - 0 1").unwrap();
+        let push = |uci| board.parse_uci_move(uci).unwrap();
+
+        assert!(is_passed_push_to_seventh(&board, &push("b6b7")));
+        assert!(!is_passed_push_to_seventh(&board, &push("h5h6")));
+        assert!(!is_passed_push_to_seventh(&board, &push("e1e2")));
+
+        let board = Board::from_fen("4k3/8/8/8/8/p7/8/4K3 b - - 0 1").unwrap();
+        assert!(is_passed_push_to_seventh(
+            &board,
+            &board.parse_uci_move("a3a2").unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_pruning_searches_fewer_nodes() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let mut nodes = Vec::new();
+
+        for enabled in [false, true] {
+            let mut board = Board::from_fen(fen).unwrap();
+            let mut engine = Engine::new();
+            engine.params = SearchParams {
+                null_move: enabled,
+                late_move_reductions: enabled,
+                reverse_futility: enabled,
+                futility: enabled,
+                late_move_pruning: enabled,
+                ..SearchParams::default()
+            };
+            let limits = SearchLimits {
+                depth: Some(4),
+                ..SearchLimits::default()
+            };
+            nodes.push(engine.search(&mut board, &limits).nodes);
+        }
+
+        assert!(nodes[1] < nodes[0], "{:?}", nodes);
+    }
+
+    #[test]
+    fn test_has_non_pawn_material() {
+        let board = Board::from_fen("4k3/pppp4/8/8/8/8/4P3/2B1K3 w - - 0 1").unwrap();
+
+        assert!(has_non_pawn_material(&board, Color::White));
+        assert!(!has_non_pawn_material(&board, Color::Black));
+    }
+}
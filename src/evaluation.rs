@@ -0,0 +1,878 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::{
+    bitboard::BitBoard,
+    board::{Board, Color, Piece},
+    consts::{
+        Square, ADJACENT_FILE_MASKS, FILE_MASKS, FORWARD_FILE_MASKS, KING_ATTACKS,
+        PASSED_PAWN_MASKS, PAWN_ATTACKS,
+    },
+    pawns::PawnTable,
+    utils::piece_attacks,
+};
+
+const PIECES: [Piece; 6] = [
+    Piece::Pawn,
+    Piece::Knight,
+    Piece::Bishop,
+    Piece::Rook,
+    Piece::Queen,
+    Piece::King,
+];
+
+/// Game phase of the starting position; it counts down to 0 as pieces
+/// come off the board.
+pub const MAX_PHASE: i32 = 24;
+const PHASE_WEIGHTS: [i32; 6] = [0, 1, 1, 2, 4, 0];
+
+/// Every tunable number of the evaluation. Tapered terms come in middlegame
+/// and endgame versions; piece-square tables are indexed by piece and then
+/// by square from White's point of view with a8 first, as printed on a
+/// diagram.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalWeights {
+    pub material_mg: [i32; 6],
+    pub material_eg: [i32; 6],
+    pub pst_mg: [[i32; 64]; 6],
+    pub pst_eg: [[i32; 64]; 6],
+    pub doubled_pawn: [i32; 2],
+    pub isolated_pawn: [i32; 2],
+    pub backward_pawn: [i32; 2],
+    pub connected_pawn: [i32; 2],
+    /// Passed pawn bonus by rank, from the owner's side.
+    pub passed_pawn_mg: [i32; 8],
+    pub passed_pawn_eg: [i32; 8],
+    /// A passer the enemy king cannot catch, with no enemy pieces left.
+    pub unstoppable_passer: [i32; 2],
+    /// Own pawns one and two ranks in front of the king.
+    pub pawn_shield: [i32; 2],
+    pub pawn_shield_far: [i32; 2],
+    /// Enemy pawns up to three ranks in front of the king.
+    pub pawn_storm: [i32; 2],
+    /// Files on or next to the king without own pawns, or without any.
+    pub king_semi_open_file: [i32; 2],
+    pub king_open_file: [i32; 2],
+    /// Danger per attacked king zone square, by attacking piece.
+    pub king_attack_weight: [i32; 6],
+    /// Percentage of the danger that counts, by number of attackers. One
+    /// piece alone rarely mates.
+    pub king_attackers_scale: [i32; 8],
+    /// Per safe square a piece attacks: not occupied by own pieces and not
+    /// attacked by enemy pawns.
+    pub mobility_mg: [i32; 6],
+    pub mobility_eg: [i32; 6],
+    pub rook_open_file: [i32; 2],
+    pub rook_half_open_file: [i32; 2],
+    /// Rook on the seventh with enemy pawns there or the king on the eighth.
+    pub rook_on_seventh: [i32; 2],
+    pub bishop_pair: [i32; 2],
+    /// Knight in enemy territory, defended by a pawn, that no enemy pawn
+    /// can chase away.
+    pub knight_outpost: [i32; 2],
+    /// Bishop on a7/h7 (a2/h2 for Black) shut in by a pawn on b6/g6.
+    pub trapped_bishop: [i32; 2],
+    /// Rook stuck in the corner by its own uncastled king.
+    pub trapped_rook: [i32; 2],
+}
+
+/// Flat view of a weight field, so all of them can be printed and parsed
+/// the same way.
+trait Params {
+    fn values(&self) -> &[i32];
+    fn values_mut(&mut self) -> &mut [i32];
+}
+
+impl<const N: usize> Params for [i32; N] {
+    fn values(&self) -> &[i32] {
+        self
+    }
+
+    fn values_mut(&mut self) -> &mut [i32] {
+        self
+    }
+}
+
+impl<const N: usize, const M: usize> Params for [[i32; N]; M] {
+    fn values(&self) -> &[i32] {
+        self.as_flattened()
+    }
+
+    fn values_mut(&mut self) -> &mut [i32] {
+        self.as_flattened_mut()
+    }
+}
+
+macro_rules! eval_params {
+    ($($field:ident),* $(,)?) => {
+        impl EvalWeights {
+            fn params(&self) -> Vec<(&'static str, &[i32])> {
+                vec![$((stringify!($field), self.$field.values())),*]
+            }
+
+            fn params_mut(&mut self) -> Vec<(&'static str, &mut [i32])> {
+                vec![$((stringify!($field), self.$field.values_mut())),*]
+            }
+        }
+    };
+}
+
+eval_params!(
+    material_mg,
+    material_eg,
+    pst_mg,
+    pst_eg,
+    doubled_pawn,
+    isolated_pawn,
+    backward_pawn,
+    connected_pawn,
+    passed_pawn_mg,
+    passed_pawn_eg,
+    unstoppable_passer,
+    pawn_shield,
+    pawn_shield_far,
+    pawn_storm,
+    king_semi_open_file,
+    king_open_file,
+    king_attack_weight,
+    king_attackers_scale,
+    mobility_mg,
+    mobility_eg,
+    rook_open_file,
+    rook_half_open_file,
+    rook_on_seventh,
+    bishop_pair,
+    knight_outpost,
+    trapped_bishop,
+    trapped_rook,
+);
+
+#[rustfmt::skip]
+impl Default for EvalWeights {
+    fn default() -> Self {
+        EvalWeights {
+            material_mg: [82, 337, 365, 477, 1025, 0],
+            material_eg: [94, 281, 297, 512, 936, 0],
+            pst_mg: [
+                [
+                      0,   0,   0,   0,   0,   0,   0,   0,
+                     98, 134,  61,  95,  68, 126,  34, -11,
+                     -6,   7,  26,  31,  65,  56,  25, -20,
+                    -14,  13,   6,  21,  23,  12,  17, -23,
+                    -27,  -2,  -5,  12,  17,   6,  10, -25,
+                    -26,  -4,  -4, -10,   3,   3,  33, -12,
+                    -35,  -1, -20, -23, -15,  24,  38, -22,
+                      0,   0,   0,   0,   0,   0,   0,   0,
+                ],
+                [
+                   -167, -89, -34, -49,  61, -97, -15,-107,
+                    -73, -41,  72,  36,  23,  62,   7, -17,
+                    -47,  60,  37,  65,  84, 129,  73,  44,
+                     -9,  17,  19,  53,  37,  69,  18,  22,
+                    -13,   4,  16,  13,  28,  19,  21,  -8,
+                    -23,  -9,  12,  10,  19,  17,  25, -16,
+                    -29, -53, -12,  -3,  -1,  18, -14, -19,
+                   -105, -21, -58, -33, -17, -28, -19, -23,
+                ],
+                [
+                    -29,   4, -82, -37, -25, -42,   7,  -8,
+                    -26,  16, -18, -13,  30,  59,  18, -47,
+                    -16,  37,  43,  40,  35,  50,  37,  -2,
+                     -4,   5,  19,  50,  37,  37,   7,  -2,
+                     -6,  13,  13,  26,  34,  12,  10,   4,
+                      0,  15,  15,  15,  14,  27,  18,  10,
+                      4,  15,  16,   0,   7,  21,  33,   1,
+                    -33,  -3, -14, -21, -13, -12, -39, -21,
+                ],
+                [
+                     32,  42,  32,  51,  63,   9,  31,  43,
+                     27,  32,  58,  62,  80,  67,  26,  44,
+                     -5,  19,  26,  36,  17,  45,  61,  16,
+                    -24, -11,   7,  26,  24,  35,  -8, -20,
+                    -36, -26, -12,  -1,   9,  -7,   6, -23,
+                    -45, -25, -16, -17,   3,   0,  -5, -33,
+                    -44, -16, -20,  -9,  -1,  11,  -6, -71,
+                    -19, -13,   1,  17,  16,   7, -37, -26,
+                ],
+                [
+                    -28,   0,  29,  12,  59,  44,  43,  45,
+                    -24, -39,  -5,   1, -16,  57,  28,  54,
+                    -13, -17,   7,   8,  29,  56,  47,  57,
+                    -27, -27, -16, -16,  -1,  17,  -2,   1,
+                     -9, -26,  -9, -10,  -2,  -4,   3,  -3,
+                    -14,   2, -11,  -2,  -5,   2,  14,   5,
+                    -35,  -8,  11,   2,   8,  15,  -3,   1,
+                     -1, -18,  -9,  10, -15, -25, -31, -50,
+                ],
+                [
+                    -65,  23,  16, -15, -56, -34,   2,  13,
+                     29,  -1, -20,  -7,  -8,  -4, -38, -29,
+                     -9,  24,   2, -16, -20,   6,  22, -22,
+                    -17, -20, -12, -27, -30, -25, -14, -36,
+                    -49,  -1, -27, -39, -46, -44, -33, -51,
+                    -14, -14, -22, -46, -44, -30, -15, -27,
+                      1,   7,  -8, -64, -43, -16,   9,   8,
+                    -15,  36,  12, -54,   8, -28,  24,  14,
+                ],
+            ],
+            pst_eg: [
+                [
+                      0,   0,   0,   0,   0,   0,   0,   0,
+                    178, 173, 158, 134, 147, 132, 165, 187,
+                     94, 100,  85,  67,  56,  53,  82,  84,
+                     32,  24,  13,   5,  -2,   4,  17,  17,
+                     13,   9,  -3,  -7,  -7,  -8,   3,  -1,
+                      4,   7,  -6,   1,   0,  -5,  -1,  -8,
+                     13,   8,   8,  10,  13,   0,   2,  -7,
+                      0,   0,   0,   0,   0,   0,   0,   0,
+                ],
+                [
+                    -58, -38, -13, -28, -31, -27, -63, -99,
+                    -25,  -8, -25,  -2,  -9, -25, -24, -52,
+                    -24, -20,  10,   9,  -1,  -9, -19, -41,
+                    -17,   3,  22,  22,  22,  11,   8, -18,
+                    -18,  -6,  16,  25,  16,  17,   4, -18,
+                    -23,  -3,  -1,  15,  10,  -3, -20, -22,
+                    -42, -20, -10,  -5,  -2, -20, -23, -44,
+                    -29, -51, -23, -15, -22, -18, -50, -64,
+                ],
+                [
+                    -14, -21, -11,  -8,  -7,  -9, -17, -24,
+                     -8,  -4,   7, -12,  -3, -13,  -4, -14,
+                      2,  -8,   0,  -1,  -2,   6,   0,   4,
+                     -3,   9,  12,   9,  14,  10,   3,   2,
+                     -6,   3,  13,  19,   7,  10,  -3,  -9,
+                    -12,  -3,   8,  10,  13,   3,  -7, -15,
+                    -14, -18,  -7,  -1,   4,  -9, -15, -27,
+                    -23,  -9, -23,  -5,  -9, -16,  -5, -17,
+                ],
+                [
+                     13,  10,  18,  15,  12,  12,   8,   5,
+                     11,  13,  13,  11,  -3,   3,   8,   3,
+                      7,   7,   7,   5,   4,  -3,  -5,  -3,
+                      4,   3,  13,   1,   2,   1,  -1,   2,
+                      3,   5,   8,   4,  -5,  -6,  -8, -11,
+                     -4,   0,  -5,  -1,  -7, -12,  -8, -16,
+                     -6,  -6,   0,   2,  -9,  -9, -11,  -3,
+                     -9,   2,   3,  -1,  -5, -13,   4, -20,
+                ],
+                [
+                     -9,  22,  22,  27,  27,  19,  10,  20,
+                    -17,  20,  32,  41,  58,  25,  30,   0,
+                    -20,   6,   9,  49,  47,  35,  19,   9,
+                      3,  22,  24,  45,  57,  40,  57,  36,
+                    -18,  28,  19,  47,  31,  34,  39,  23,
+                    -16, -27,  15,   6,   9,  17,  10,   5,
+                    -22, -23, -30, -16, -16, -23, -36, -32,
+                    -33, -28, -22, -43,  -5, -32, -20, -41,
+                ],
+                [
+                    -74, -35, -18, -18, -11,  15,   4, -17,
+                    -12,  17,  14,  17,  17,  38,  23,  11,
+                     10,  17,  23,  15,  20,  45,  44,  13,
+                     -8,  22,  24,  27,  26,  33,  26,   3,
+                    -18,  -4,  21,  24,  27,  23,   9, -11,
+                    -19,  -3,  11,  21,  23,  16,   7,  -9,
+                    -27, -11,   4,  13,  14,   4,  -5, -17,
+                    -53, -34, -21, -11, -28, -14, -24, -43,
+                ],
+            ],
+            doubled_pawn: [-10, -25],
+            isolated_pawn: [-12, -15],
+            backward_pawn: [-8, -12],
+            connected_pawn: [8, 6],
+            passed_pawn_mg: [0, 2, 5, 10, 20, 35, 55, 0],
+            passed_pawn_eg: [0, 10, 15, 25, 45, 75, 120, 0],
+            unstoppable_passer: [0, 500],
+            pawn_shield: [15, 0],
+            pawn_shield_far: [8, 0],
+            pawn_storm: [-8, 0],
+            king_semi_open_file: [-12, 0],
+            king_open_file: [-25, 0],
+            king_attack_weight: [0, 8, 8, 12, 20, 0],
+            king_attackers_scale: [0, 0, 50, 75, 88, 94, 97, 99],
+            mobility_mg: [0, 4, 5, 2, 1, 0],
+            mobility_eg: [0, 4, 5, 4, 2, 0],
+            rook_open_file: [25, 10],
+            rook_half_open_file: [12, 6],
+            rook_on_seventh: [20, 30],
+            bishop_pair: [30, 50],
+            knight_outpost: [20, 10],
+            trapped_bishop: [-100, -100],
+            trapped_rook: [-40, -10],
+        }
+    }
+}
+
+/// Prints one `name` line per parameter followed by its values, eight per
+/// line, in the format `FromStr` reads back.
+impl fmt::Display for EvalWeights {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, values) in self.params() {
+            writeln!(f, "{}", name)?;
+            for row in values.chunks(8) {
+                let row: Vec<String> = row.iter().map(|v| format!("{:5}", v)).collect();
+                writeln!(f, "   {}", row.join(""))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads weights in the format written by `Display`. Any whitespace layout
+/// works as long as each name is followed by exactly its number of values;
+/// parameters that are left out keep their default.
+impl FromStr for EvalWeights {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut weights = EvalWeights::default();
+        let mut params = weights.params_mut();
+        let mut current: Option<(usize, usize)> = None;
+
+        let check_count =
+            |params: &[(&str, &mut [i32])], current: Option<(usize, usize)>| match current {
+                Some((idx, filled)) if filled != params[idx].1.len() => Err(format!(
+                    "{}: expected {} values, got {}",
+                    params[idx].0,
+                    params[idx].1.len(),
+                    filled
+                )),
+                _ => Ok(()),
+            };
+
+        for token in s.split_whitespace() {
+            if let Ok(value) = token.parse::<i32>() {
+                let (idx, filled) = current.as_mut().ok_or("value before any parameter name")?;
+                let (name, values) = &mut params[*idx];
+                let slot = values
+                    .get_mut(*filled)
+                    .ok_or_else(|| format!("{}: too many values", name))?;
+                *slot = value;
+                *filled += 1;
+            } else {
+                check_count(&params, current)?;
+                let idx = params
+                    .iter()
+                    .position(|(name, _)| *name == token)
+                    .ok_or_else(|| format!("unknown parameter: {}", token))?;
+                current = Some((idx, 0));
+            }
+        }
+
+        check_count(&params, current)?;
+        drop(params);
+        Ok(weights)
+    }
+}
+
+/// Static evaluation with the weights it uses and its pawn hash table.
+#[derive(Debug, Clone, Default)]
+pub struct Evaluator {
+    weights: EvalWeights,
+    pawn_table: PawnTable,
+}
+
+impl Evaluator {
+    /// Replaces the weights, dropping pawn terms cached with the old ones.
+    pub fn set_weights(&mut self, weights: EvalWeights) {
+        self.weights = weights;
+        self.pawn_table.clear();
+    }
+
+    /// Scores `board` in centipawns from the side to move's perspective.
+    pub fn evaluate(&mut self, board: &Board) -> i32 {
+        let pawns = self.pawn_table.probe(board, &self.weights);
+        let w = &self.weights;
+        let mut mg = 0;
+        let mut eg = 0;
+
+        for color in [Color::White, Color::Black] {
+            let sign = if color == Color::White { 1 } else { -1 };
+            // The tables are laid out a8..h1, so White's squares are
+            // mirrored vertically and Black's map onto them directly.
+            let flip = if color == Color::White { 56 } else { 0 };
+
+            for (idx, &piece) in PIECES.iter().enumerate() {
+                for sq in board.pieces_of(color, piece) {
+                    let pst = sq as usize ^ flip;
+                    mg += sign * (w.material_mg[idx] + w.pst_mg[idx][pst]);
+                    eg += sign * (w.material_eg[idx] + w.pst_eg[idx][pst]);
+                }
+            }
+        }
+
+        mg += pawns.mg;
+        eg += pawns.eg;
+
+        for color in [Color::White, Color::Black] {
+            let sign = if color == Color::White { 1 } else { -1 };
+            let count = unstoppable_passers(board, color, pawns.passed[color as usize]);
+            mg += sign * count * w.unstoppable_passer[0];
+            eg += sign * count * w.unstoppable_passer[1];
+
+            let [king_mg, king_eg] = king_safety(board, color, w);
+            mg += sign * king_mg;
+            eg += sign * king_eg;
+
+            let [activity_mg, activity_eg] = piece_activity(board, color, w);
+            mg += sign * activity_mg;
+            eg += sign * activity_eg;
+        }
+
+        let phase = game_phase(board);
+        let score = (mg * phase + eg * (MAX_PHASE - phase)) / MAX_PHASE;
+
+        match board.turn {
+            Color::White => score,
+            Color::Black => -score,
+        }
+    }
+}
+
+/// Counts the passed pawns of `color` that queen by force: the opponent has
+/// only pawns left, the path is clear, and the enemy king is outside the
+/// pawn's square.
+fn unstoppable_passers(board: &Board, color: Color, passed: BitBoard) -> i32 {
+    let them = color.opposite();
+    let their_pieces = [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen]
+        .iter()
+        .any(|&piece| !board.pieces_of(them, piece).is_empty());
+
+    let Some(king) = board.pieces_of(them, Piece::King).next_square() else {
+        return 0;
+    };
+
+    if their_pieces {
+        return 0;
+    }
+
+    let mut count = 0;
+
+    for sq in passed {
+        if FORWARD_FILE_MASKS[color as usize][sq as usize] & board.occupied.0 != 0 {
+            continue;
+        }
+
+        let (rank, promotion) = match color {
+            Color::White => (sq.rank() as i32, Square::from_index(sq.file() as u8 + 56)),
+            Color::Black => (7 - sq.rank() as i32, Square::from_index(sq.file() as u8)),
+        };
+
+        // A pawn on its starting rank can push two squares at once.
+        let pawn_moves = (7 - rank).min(5);
+        let king_moves = distance(king, promotion) - (board.turn == them) as i32;
+
+        if king_moves > pawn_moves {
+            count += 1;
+        }
+    }
+
+    count
+}
+
+/// Pawn cover, open files and enemy attacks around the king of `color`, as
+/// middlegame and endgame scores for that side.
+fn king_safety(board: &Board, color: Color, w: &EvalWeights) -> [i32; 2] {
+    let Some(king) = board.pieces_of(color, Piece::King).next_square() else {
+        return [0, 0];
+    };
+
+    let them = color.opposite();
+    let ours = board.pieces_of(color, Piece::Pawn).0;
+    let theirs = board.pieces_of(them, Piece::Pawn).0;
+    let file = king.file() as usize;
+    let rank = king.rank() as i32;
+    let ahead = |ranks: i32| match color {
+        Color::White => rank_mask(rank + ranks),
+        Color::Black => rank_mask(rank - ranks),
+    };
+
+    let mut score = [0, 0];
+    let mut add = |term: [i32; 2], count: u32| {
+        score[0] += term[0] * count as i32;
+        score[1] += term[1] * count as i32;
+    };
+
+    let files = FILE_MASKS[file] | ADJACENT_FILE_MASKS[file];
+    add(w.pawn_shield, (ours & files & ahead(1)).count_ones());
+    add(w.pawn_shield_far, (ours & files & ahead(2)).count_ones());
+    add(
+        w.pawn_storm,
+        (theirs & files & (ahead(1) | ahead(2) | ahead(3))).count_ones(),
+    );
+
+    for &mask in &FILE_MASKS[file.saturating_sub(1)..=(file + 1).min(7)] {
+        if mask & (ours | theirs) == 0 {
+            add(w.king_open_file, 1);
+        } else if mask & ours == 0 {
+            add(w.king_semi_open_file, 1);
+        }
+    }
+
+    let zone = KING_ATTACKS[king as usize] | king.bb();
+    let mut attackers = 0;
+    let mut danger = 0;
+
+    for piece in [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen] {
+        for sq in board.pieces_of(them, piece) {
+            let attacks = piece_attacks(piece, them, sq, board.occupied);
+
+            let hits = (attacks & zone).count() as i32;
+            if hits > 0 {
+                attackers += 1;
+                danger += hits * w.king_attack_weight[piece as usize];
+            }
+        }
+    }
+
+    score[0] -= danger * w.king_attackers_scale[attackers.min(7)] / 100;
+    score
+}
+
+/// Mobility and placement of the knights, bishops, rooks and queens of
+/// `color`, as middlegame and endgame scores for that side.
+fn piece_activity(board: &Board, color: Color, w: &EvalWeights) -> [i32; 2] {
+    let us = color as usize;
+    let them = color.opposite();
+    let ours = board.pieces_of(color, Piece::Pawn).0;
+    let theirs = board.pieces_of(them, Piece::Pawn).0;
+    let own_pieces = match color {
+        Color::White => board.white_occupied,
+        Color::Black => board.black_occupied,
+    };
+
+    let mut enemy_pawn_attacks = 0;
+    for sq in BitBoard(theirs) {
+        enemy_pawn_attacks |= PAWN_ATTACKS[them as usize][sq as usize];
+    }
+    let safe = !own_pieces & BitBoard(!enemy_pawn_attacks);
+
+    // Ranks as seen from `color`: the seventh and eighth, and the enemy half
+    // where outposts count.
+    let relative = |rank: i32| match color {
+        Color::White => rank_mask(rank),
+        Color::Black => rank_mask(7 - rank),
+    };
+
+    let mut score = [0, 0];
+    let mut add = |term: [i32; 2], count: i32| {
+        score[0] += term[0] * count;
+        score[1] += term[1] * count;
+    };
+
+    for piece in [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen] {
+        let idx = piece as usize;
+
+        for sq in board.pieces_of(color, piece) {
+            let attacks = piece_attacks(piece, color, sq, board.occupied);
+            let mobility = (attacks & safe).count() as i32;
+            add([w.mobility_mg[idx], w.mobility_eg[idx]], mobility);
+
+            let file = FILE_MASKS[sq.file() as usize];
+
+            match piece {
+                Piece::Knight => {
+                    let enemy_half = relative(3) | relative(4) | relative(5);
+                    let defended = PAWN_ATTACKS[1 - us][sq as usize] & ours != 0;
+                    let chasers = PASSED_PAWN_MASKS[us][sq as usize]
+                        & ADJACENT_FILE_MASKS[sq.file() as usize];
+
+                    if sq.bb().0 & enemy_half != 0 && defended && chasers & theirs == 0 {
+                        add(w.knight_outpost, 1);
+                    }
+                }
+                Piece::Bishop => {
+                    let (corner, blocker) = match (color, sq) {
+                        (Color::White, Square::A7) => (true, Square::B6),
+                        (Color::White, Square::H7) => (true, Square::G6),
+                        (Color::Black, Square::A2) => (true, Square::B3),
+                        (Color::Black, Square::H2) => (true, Square::G3),
+                        _ => (false, sq),
+                    };
+
+                    if corner && theirs & blocker.bb().0 != 0 {
+                        add(w.trapped_bishop, 1);
+                    }
+                }
+                Piece::Rook => {
+                    if file & (ours | theirs) == 0 {
+                        add(w.rook_open_file, 1);
+                    } else if file & ours == 0 {
+                        add(w.rook_half_open_file, 1);
+                    }
+
+                    let enemy_king = board.pieces_of(them, Piece::King).0;
+                    if sq.bb().0 & relative(6) != 0
+                        && (theirs & relative(6) != 0 || enemy_king & relative(7) != 0)
+                    {
+                        add(w.rook_on_seventh, 1);
+                    }
+
+                    if rook_trapped_by_king(board, color, sq) && mobility <= 3 {
+                        add(w.trapped_rook, 1);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if board.pieces_of(color, Piece::Bishop).count() >= 2 {
+        add(w.bishop_pair, 1);
+    }
+
+    score
+}
+
+/// Whether the rook on `sq` is on its back rank, cut off from the centre by
+/// its own king standing between it and the d/e files without having
+/// castled.
+fn rook_trapped_by_king(board: &Board, color: Color, sq: Square) -> bool {
+    let back_rank = match color {
+        Color::White => 0,
+        Color::Black => 7,
+    };
+    let Some(king) = board.pieces_of(color, Piece::King).next_square() else {
+        return false;
+    };
+
+    if sq.rank() as usize != back_rank || king.rank() as usize != back_rank {
+        return false;
+    }
+
+    let (rook_file, king_file) = (sq.file() as usize, king.file() as usize);
+    // King on f1/g1 with the rook further right, or b1/c1 with it left.
+    (matches!(king_file, 5 | 6) && rook_file > king_file)
+        || (matches!(king_file, 1 | 2) && rook_file < king_file)
+}
+
+fn rank_mask(rank: i32) -> u64 {
+    if (0..8).contains(&rank) {
+        0xffu64 << (rank * 8)
+    } else {
+        0
+    }
+}
+
+/// Number of king moves between two squares.
+fn distance(a: Square, b: Square) -> i32 {
+    let files = (a.file() as i32 - b.file() as i32).abs();
+    let ranks = (a.rank() as i32 - b.rank() as i32).abs();
+    files.max(ranks)
+}
+
+/// `MAX_PHASE` with all minor and major pieces on the board, 0 with none.
+/// Early promotions can push the count past the maximum, so it is capped.
+pub fn game_phase(board: &Board) -> i32 {
+    let phase: i32 = PIECES
+        .iter()
+        .enumerate()
+        .map(|(idx, &piece)| {
+            let count = board.pieces_of(Color::White, piece).count()
+                + board.pieces_of(Color::Black, piece).count();
+            count as i32 * PHASE_WEIGHTS[idx]
+        })
+        .sum();
+
+    phase.min(MAX_PHASE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Swaps the colours of a position by flipping the board vertically.
+    fn mirror(fen: &str) -> String {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        let placement: Vec<String> = fields[0]
+            .split('/')
+            .rev()
+            .map(|rank| {
+                rank.chars()
+                    .map(|c| {
+                        if c.is_ascii_uppercase() {
+                            c.to_ascii_lowercase()
+                        } else {
+                            c.to_ascii_uppercase()
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        let turn = if fields[1] == "w" { "b" } else { "w" };
+
+        format!("{} {} - - 0 1", placement.join("/"), turn)
+    }
+
+    #[test]
+    fn test_start_position_is_balanced() {
+        let mut evaluator = Evaluator::default();
+        let board = Board::default();
+
+        assert_eq!(evaluator.evaluate(&board), 0);
+        assert_eq!(game_phase(&board), MAX_PHASE);
+    }
+
+    #[test]
+    fn test_evaluation_is_colour_symmetric() {
+        let mut evaluator = Evaluator::default();
+        let fens = [
+            "r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/3P1N2/PPP2PPP/RNBQK2R w - - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "4k3/8/8/3q4/8/8/8/3R1K2 b - - 0 1",
+        ];
+
+        for fen in fens {
+            let board = Board::from_fen(fen).unwrap();
+            let mirrored = Board::from_fen(&mirror(fen)).unwrap();
+            assert_eq!(
+                evaluator.evaluate(&board),
+                evaluator.evaluate(&mirrored),
+                "{}",
+                fen
+            );
+        }
+    }
+
+    #[test]
+    fn test_extra_material_is_good_for_its_owner() {
+        let mut evaluator = Evaluator::default();
+        let white_to_move = Board::from_fen("4k3/8/8/3q4/8/8/8/3R1K2 w - - 0 1").unwrap();
+        let black_to_move = Board::from_fen("4k3/8/8/3q4/8/8/8/3R1K2 b - - 0 1").unwrap();
+
+        assert!(evaluator.evaluate(&white_to_move) < -300);
+        assert!(evaluator.evaluate(&black_to_move) > 300);
+    }
+
+    #[test]
+    fn test_phase_tapers_to_endgame() {
+        let bare_kings = Board::from_fen("4k3/8/8/8/8/8/8/3K4 w - - 0 1").unwrap();
+        let rooks = Board::from_fen("r3k3/8/8/8/8/8/8/3K3R w - - 0 1").unwrap();
+
+        assert_eq!(game_phase(&bare_kings), 0);
+        assert_eq!(game_phase(&rooks), 4);
+    }
+
+    #[test]
+    fn test_unstoppable_passer() {
+        let count = |fen: &str| {
+            let board = Board::from_fen(fen).unwrap();
+            unstoppable_passers(
+                &board,
+                Color::White,
+                board.pieces_of(Color::White, Piece::Pawn),
+            )
+        };
+
+        assert_eq!(count("7K/8/8/8/P7/8/8/7k w - - 0 1"), 1);
+        // Inside the square, or just outside it but on the move.
+        assert_eq!(count("7K/8/8/1k6/P7/8/8/8 w - - 0 1"), 0);
+        assert_eq!(count("7K/8/8/8/P4k2/8/8/8 b - - 0 1"), 0);
+        assert_eq!(count("7K/8/8/8/P4k2/8/8/8 w - - 0 1"), 1);
+        // A knight can still stop it.
+        assert_eq!(count("7K/8/8/8/P7/8/8/6nk w - - 0 1"), 0);
+    }
+
+    #[test]
+    fn test_pawn_shield_and_open_files() {
+        let w = EvalWeights::default();
+        let safety = |fen: &str| king_safety(&Board::from_fen(fen).unwrap(), Color::White, &w);
+
+        let sheltered = safety("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1");
+        let exposed = safety("6k1/5ppp/8/8/8/8/8/6K1 w - - 0 1");
+
+        assert_eq!(sheltered[0], 3 * w.pawn_shield[0]);
+        assert_eq!(exposed[0], 3 * w.king_semi_open_file[0]);
+    }
+
+    #[test]
+    fn test_king_attackers() {
+        let w = EvalWeights::default();
+        // Queen and knight both hit the squares around g1; the f2-g2-h2
+        // shield is intact so only the attack terms differ.
+        let attacked = Board::from_fen("6k1/8/8/8/8/5n2/5PPP/4q1K1 w - - 0 1").unwrap();
+        let queen_only = Board::from_fen("6k1/8/8/8/8/8/5PPP/4q1K1 w - - 0 1").unwrap();
+        let base = 3 * w.pawn_shield[0];
+
+        assert_eq!(king_safety(&queen_only, Color::White, &w)[0], base);
+        assert!(king_safety(&attacked, Color::White, &w)[0] < base);
+    }
+
+    #[test]
+    fn test_mobility_prefers_central_knight() {
+        let w = EvalWeights::default();
+        let activity = |fen: &str| piece_activity(&Board::from_fen(fen).unwrap(), Color::White, &w);
+
+        let central = activity("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1");
+        let corner = activity("4k3/8/8/8/8/8/8/N3K3 w - - 0 1");
+        assert_eq!(central[0], 8 * w.mobility_mg[Piece::Knight as usize]);
+        assert_eq!(corner[0], 2 * w.mobility_mg[Piece::Knight as usize]);
+
+        // Squares covered by enemy pawns do not count.
+        let harassed = activity("4k3/3p4/8/8/3N4/8/8/4K3 w - - 0 1");
+        assert_eq!(harassed[0], 6 * w.mobility_mg[Piece::Knight as usize]);
+    }
+
+    #[test]
+    fn test_rook_files_and_seventh_rank() {
+        let w = EvalWeights::default();
+        let activity = |fen: &str| piece_activity(&Board::from_fen(fen).unwrap(), Color::White, &w);
+        let rook_mobility = |n: i32| n * w.mobility_mg[Piece::Rook as usize];
+
+        let open = activity("4k3/8/8/8/8/8/8/R3K3 w - - 0 1");
+        let half_open = activity("4k3/p7/8/8/8/8/8/R3K3 w - - 0 1");
+        let seventh = activity("4k3/R7/8/8/8/8/8/4K3 w - - 0 1");
+
+        assert_eq!(open[0], rook_mobility(10) + w.rook_open_file[0]);
+        assert_eq!(half_open[0], rook_mobility(9) + w.rook_half_open_file[0]);
+        assert_eq!(
+            seventh[0],
+            rook_mobility(14) + w.rook_open_file[0] + w.rook_on_seventh[0]
+        );
+    }
+
+    #[test]
+    fn test_bishop_pair_outpost_and_trapped_pieces() {
+        let w = EvalWeights::default();
+        let activity = |fen: &str| piece_activity(&Board::from_fen(fen).unwrap(), Color::White, &w);
+
+        let pair = activity("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1");
+        let single = activity("4k3/8/8/8/8/8/8/2B1K3 w - - 0 1");
+        assert!(pair[1] - single[1] > w.bishop_pair[1]);
+
+        // d5 is defended by e4 and no black pawn can ever attack it.
+        let outpost = activity("4k3/8/8/3N4/4P3/8/8/4K3 w - - 0 1");
+        let chased = activity("4k3/2p5/8/3N4/4P3/8/8/4K3 w - - 0 1");
+        assert!(outpost[0] - chased[0] >= w.knight_outpost[0]);
+
+        let trapped = activity("4k3/B7/1p6/8/8/8/8/4K3 w - - 0 1");
+        assert!(trapped[0] <= w.trapped_bishop[0] + 2 * w.mobility_mg[Piece::Bishop as usize]);
+
+        let stuck_rook = activity("4k3/8/8/8/8/8/6PP/5K1R w - - 0 1");
+        let castled_rook = activity("4k3/8/8/8/8/8/6PP/5RK1 w - - 0 1");
+        assert!(stuck_rook[0] < castled_rook[0]);
+    }
+
+    #[test]
+    fn test_weights_round_trip_through_text() {
+        let mut weights = EvalWeights::default();
+        weights.material_mg[Piece::Knight as usize] = 300;
+        weights.pst_eg[5][63] = -7;
+
+        let parsed: EvalWeights = weights.to_string().parse().unwrap();
+        assert_eq!(parsed, weights);
+
+        let partial: EvalWeights = "material_eg 1 2 3 4 5 6".parse().unwrap();
+        assert_eq!(partial.material_eg, [1, 2, 3, 4, 5, 6]);
+        assert_eq!(partial.pst_mg, EvalWeights::default().pst_mg);
+    }
+
+    #[test]
+    fn test_weights_parse_errors() {
+        assert!("material_mg 1 2 3".parse::<EvalWeights>().is_err());
+        assert!("material_mg 1 2 3 4 5 6 7".parse::<EvalWeights>().is_err());
+        assert!("mobility 1 2".parse::<EvalWeights>().is_err());
+        assert!("5 material_mg".parse::<EvalWeights>().is_err());
+    }
+}
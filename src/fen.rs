@@ -0,0 +1,629 @@
+use std::fmt::{self, Write};
+
+use crate::{
+    board::{Board, Color, Piece},
+    consts::{
+        Rank, Square, B_KINGSIDE_RIGHTS, B_QUEENSIDE_RIGHTS, W_KINGSIDE_RIGHTS, W_QUEENSIDE_RIGHTS,
+    },
+};
+
+/// Why a FEN string was rejected. `index` is the byte offset in the FEN of
+/// the character or field at fault.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    /// Wrong number of fields: 6, or 4 to 6 in lenient mode.
+    FieldCount(usize),
+    /// Not a piece letter, a digit from 1 to 8 or `/`.
+    InvalidPiece {
+        index: usize,
+        found: char,
+    },
+    /// The placement does not have eight ranks.
+    RankCount(usize),
+    /// A rank that does not describe exactly eight squares.
+    RankLength {
+        rank: Rank,
+        index: usize,
+    },
+    /// Each side needs exactly one king.
+    KingCount {
+        color: Color,
+        count: u32,
+    },
+    PawnOnBackRank(Square),
+    InvalidSideToMove {
+        index: usize,
+    },
+    /// Not one of `KQkq`, repeated, or mixed with `-`.
+    InvalidCastling {
+        index: usize,
+        found: char,
+    },
+    /// A castling right whose king or rook is not on its home square.
+    ImpossibleCastling {
+        index: usize,
+        found: char,
+    },
+    InvalidEnPassant {
+        index: usize,
+    },
+    /// There is no pawn that can just have moved two squares past it.
+    ImplausibleEnPassant(Square),
+    InvalidHalfmoveClock {
+        index: usize,
+    },
+    InvalidFullmoveNumber {
+        index: usize,
+    },
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FenError::FieldCount(count) => write!(f, "expected 6 fields, got {}", count),
+            FenError::InvalidPiece { index, found } => {
+                write!(f, "invalid piece {:?} at index {}", found, index)
+            }
+            FenError::RankCount(count) => write!(f, "expected 8 ranks, got {}", count),
+            FenError::RankLength { rank, index } => {
+                write!(
+                    f,
+                    "rank {} at index {} is not 8 squares long",
+                    *rank as u8 + 1,
+                    index
+                )
+            }
+            FenError::KingCount { color, count } => {
+                write!(f, "{:?} has {} kings", color, count)
+            }
+            FenError::PawnOnBackRank(sq) => write!(f, "pawn on back rank square {}", sq),
+            FenError::InvalidSideToMove { index } => {
+                write!(f, "invalid side to move at index {}", index)
+            }
+            FenError::InvalidCastling { index, found } => {
+                write!(f, "invalid castling right {:?} at index {}", found, index)
+            }
+            FenError::ImpossibleCastling { index, found } => {
+                write!(
+                    f,
+                    "castling right {:?} at index {} without king and rook at home",
+                    found, index
+                )
+            }
+            FenError::InvalidEnPassant { index } => {
+                write!(f, "invalid en passant square at index {}", index)
+            }
+            FenError::ImplausibleEnPassant(sq) => {
+                write!(f, "no pawn can have just passed en passant square {}", sq)
+            }
+            FenError::InvalidHalfmoveClock { index } => {
+                write!(f, "invalid halfmove clock at index {}", index)
+            }
+            FenError::InvalidFullmoveNumber { index } => {
+                write!(f, "invalid fullmove number at index {}", index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+impl Board {
+    pub fn from_fen(fen: &str) -> Result<Board, FenError> {
+        parse_fen(fen, false)
+    }
+
+    /// Like `from_fen`, but the clocks may be left out, as in EPD records
+    /// or FENs cut down to four fields, and castling rights whose king or
+    /// rook has left its home square are dropped.
+    pub fn from_fen_lenient(fen: &str) -> Result<Board, FenError> {
+        parse_fen(fen, true)
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+
+            for file in 0..8 {
+                match self.piece_on_square(Square::from_index(rank * 8 + file)) {
+                    Some((piece, color)) => {
+                        if empty > 0 {
+                            write!(fen, "{}", empty).unwrap();
+                            empty = 0;
+                        }
+                        fen.push(match color {
+                            Color::White => piece.to_char().to_ascii_uppercase(),
+                            Color::Black => piece.to_char(),
+                        });
+                    }
+                    None => empty += 1,
+                }
+            }
+
+            if empty > 0 {
+                write!(fen, "{}", empty).unwrap();
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+
+        fen.push_str(match self.turn {
+            Color::White => " w ",
+            Color::Black => " b ",
+        });
+
+        if self.castling_rights == 0 {
+            fen.push('-');
+        }
+        for (right, c) in CASTLING_CHARS {
+            if self.castling_rights & right != 0 {
+                fen.push(c);
+            }
+        }
+
+        match self.en_passant_square {
+            Some(sq) => write!(fen, " {}", sq).unwrap(),
+            None => fen.push_str(" -"),
+        }
+
+        write!(fen, " {} {}", self.halfmove_clock, self.fullmove_number).unwrap();
+        fen
+    }
+}
+
+const CASTLING_CHARS: [(u8, char); 4] = [
+    (W_KINGSIDE_RIGHTS, 'K'),
+    (W_QUEENSIDE_RIGHTS, 'Q'),
+    (B_KINGSIDE_RIGHTS, 'k'),
+    (B_QUEENSIDE_RIGHTS, 'q'),
+];
+
+fn parse_fen(fen: &str, lenient: bool) -> Result<Board, FenError> {
+    let fields = split_fields(fen);
+    let count_ok = match lenient {
+        true => (4..=6).contains(&fields.len()),
+        false => fields.len() == 6,
+    };
+    if !count_ok {
+        return Err(FenError::FieldCount(fields.len()));
+    }
+
+    let mut board = Board::empty();
+
+    parse_placement(&mut board, fields[0])?;
+
+    let (idx, side) = fields[1];
+    board.turn = match side {
+        "w" => Color::White,
+        "b" => Color::Black,
+        _ => return Err(FenError::InvalidSideToMove { index: idx }),
+    };
+
+    let (idx, castling) = fields[2];
+    if castling != "-" {
+        for (offset, c) in castling.char_indices() {
+            let right = CASTLING_CHARS
+                .iter()
+                .find(|&&(_, right_char)| right_char == c)
+                .map(|&(right, _)| right)
+                .filter(|&right| board.castling_rights & right == 0)
+                .ok_or(FenError::InvalidCastling {
+                    index: idx + offset,
+                    found: c,
+                })?;
+
+            if castling_is_possible(&board, right) {
+                board.castling_rights |= right;
+            } else if !lenient {
+                return Err(FenError::ImpossibleCastling {
+                    index: idx + offset,
+                    found: c,
+                });
+            }
+        }
+    }
+
+    let (idx, en_passant) = fields[3];
+    if en_passant != "-" {
+        let sq = Square::from_name(en_passant).ok_or(FenError::InvalidEnPassant { index: idx })?;
+        if !en_passant_is_plausible(&board, sq) {
+            return Err(FenError::ImplausibleEnPassant(sq));
+        }
+        board.en_passant_square = Some(sq);
+    }
+
+    if let Some(&(idx, clock)) = fields.get(4) {
+        board.halfmove_clock = clock
+            .parse()
+            .map_err(|_| FenError::InvalidHalfmoveClock { index: idx })?;
+    }
+
+    if let Some(&(idx, number)) = fields.get(5) {
+        board.fullmove_number = number
+            .parse()
+            .ok()
+            .filter(|&n| n > 0)
+            .ok_or(FenError::InvalidFullmoveNumber { index: idx })?;
+    }
+
+    board.zobrist_hash = board.compute_hash();
+    board.pawn_key = board.compute_pawn_key();
+
+    Ok(board)
+}
+
+/// Whether the king and rook that `right` castles with are on their home
+/// squares.
+fn castling_is_possible(board: &Board, right: u8) -> bool {
+    let (color, king, rook) = match right {
+        W_KINGSIDE_RIGHTS => (Color::White, Square::E1, Square::H1),
+        W_QUEENSIDE_RIGHTS => (Color::White, Square::E1, Square::A1),
+        B_KINGSIDE_RIGHTS => (Color::Black, Square::E8, Square::H8),
+        _ => (Color::Black, Square::E8, Square::A8),
+    };
+
+    board.piece_on_square(king) == Some((Piece::King, color))
+        && board.piece_on_square(rook) == Some((Piece::Rook, color))
+}
+
+/// The whitespace separated fields of `fen` with their byte offsets.
+fn split_fields(fen: &str) -> Vec<(usize, &str)> {
+    let mut fields = Vec::new();
+    let mut start = None;
+
+    for (idx, c) in fen.char_indices().chain([(fen.len(), ' ')]) {
+        match (start, c.is_whitespace()) {
+            (None, false) => start = Some(idx),
+            (Some(from), true) => {
+                fields.push((from, &fen[from..idx]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+
+    fields
+}
+
+fn parse_placement(board: &mut Board, (start, placement): (usize, &str)) -> Result<(), FenError> {
+    let ranks: Vec<&str> = placement.split('/').collect();
+    if ranks.len() != 8 {
+        return Err(FenError::RankCount(ranks.len()));
+    }
+
+    let mut idx = start;
+    for (row, text) in ranks.into_iter().enumerate() {
+        let rank = Rank::index(7 - row);
+        let mut file = 0;
+
+        for (offset, c) in text.char_indices() {
+            if let Some(skip) = c.to_digit(10).filter(|d| (1..=8).contains(d)) {
+                file += skip as usize;
+            } else {
+                let piece =
+                    Piece::from_char(c.to_ascii_lowercase()).ok_or(FenError::InvalidPiece {
+                        index: idx + offset,
+                        found: c,
+                    })?;
+                let color = match c.is_ascii_uppercase() {
+                    true => Color::White,
+                    false => Color::Black,
+                };
+
+                if file < 8 {
+                    let sq = Square::from_index((rank as usize * 8 + file) as u8);
+                    if piece == Piece::Pawn && matches!(rank, Rank::First | Rank::Eighth) {
+                        return Err(FenError::PawnOnBackRank(sq));
+                    }
+                    board.add_piece(sq, piece, color);
+                }
+                file += 1;
+            }
+
+            if file > 8 {
+                return Err(FenError::RankLength { rank, index: idx });
+            }
+        }
+
+        if file != 8 {
+            return Err(FenError::RankLength { rank, index: idx });
+        }
+        idx += text.len() + 1;
+    }
+
+    for color in [Color::White, Color::Black] {
+        let count = board.pieces_of(color, Piece::King).count();
+        if count != 1 {
+            return Err(FenError::KingCount { color, count });
+        }
+    }
+
+    Ok(())
+}
+
+/// The pawn that just moved two squares must stand in front of `sq`, with
+/// `sq` and the square it came from empty.
+fn en_passant_is_plausible(board: &Board, sq: Square) -> bool {
+    let (rank, pawn, origin) = match board.turn {
+        Color::White => (Rank::Sixth, sq.try_offset(0, -1), sq.try_offset(0, 1)),
+        Color::Black => (Rank::Third, sq.try_offset(0, 1), sq.try_offset(0, -1)),
+    };
+    let (Some(pawn), Some(origin)) = (pawn, origin) else {
+        return false;
+    };
+
+    sq.rank() == rank
+        && board.piece_on_square(pawn) == Some((Piece::Pawn, board.turn.opposite()))
+        && board.piece_on_square(sq).is_none()
+        && board.piece_on_square(origin).is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    #[test]
+    fn test_from_fen() {
+        let board = Board::from_fen(START_FEN).unwrap();
+
+        assert_eq!(
+            board.pieces_of(Color::White, Piece::Pawn).0,
+            0x000000000000FF00
+        );
+        assert_eq!(
+            board.pieces_of(Color::White, Piece::Knight).0,
+            0x0000000000000042
+        );
+        assert_eq!(
+            board.pieces_of(Color::White, Piece::Bishop).0,
+            0x0000000000000024
+        );
+        assert_eq!(
+            board.pieces_of(Color::White, Piece::Rook).0,
+            0x0000000000000081
+        );
+        assert_eq!(
+            board.pieces_of(Color::White, Piece::Queen).0,
+            0x0000000000000008
+        );
+        assert_eq!(
+            board.pieces_of(Color::White, Piece::King).0,
+            0x0000000000000010
+        );
+
+        assert_eq!(
+            board.pieces_of(Color::Black, Piece::Pawn).0,
+            0x00FF000000000000
+        );
+        assert_eq!(
+            board.pieces_of(Color::Black, Piece::Knight).0,
+            0x4200000000000000
+        );
+        assert_eq!(
+            board.pieces_of(Color::Black, Piece::Bishop).0,
+            0x2400000000000000
+        );
+        assert_eq!(
+            board.pieces_of(Color::Black, Piece::Rook).0,
+            0x8100000000000000
+        );
+        assert_eq!(
+            board.pieces_of(Color::Black, Piece::Queen).0,
+            0x0800000000000000
+        );
+        assert_eq!(
+            board.pieces_of(Color::Black, Piece::King).0,
+            0x1000000000000000
+        );
+
+        assert_eq!(board.white_occupied.0, 0x000000000000FFFF);
+        assert_eq!(board.black_occupied.0, 0xFFFF000000000000);
+        assert_eq!(board.occupied.0, 0xFFFF00000000FFFF);
+        assert_eq!(board.empty.0, !0xFFFF00000000FFFF);
+
+        assert_eq!(board.turn, Color::White);
+        assert_eq!(board.castling_rights, 15);
+        assert_eq!(board.en_passant_square, None);
+        assert_eq!(board.halfmove_clock, 0);
+        assert_eq!(board.fullmove_number, 1);
+        assert_eq!(board.zobrist_hash, Board::default().zobrist_hash);
+
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w - - 0 1").unwrap();
+        assert_eq!(board.castling_rights, 0);
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w Kq - 0 1").unwrap();
+        assert_eq!(board.castling_rights, 0b1001);
+    }
+
+    #[test]
+    fn test_fen_round_trip() {
+        for fen in [
+            START_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w Kq f6 0 3",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 37 112",
+        ] {
+            assert_eq!(Board::from_fen(fen).unwrap().to_fen(), fen);
+        }
+    }
+
+    #[test]
+    fn test_to_fen_follows_moves() {
+        let mut board = Board::default();
+        assert_eq!(board.to_fen(), START_FEN);
+
+        let moves = board.generate_legal_moves();
+        let e4 = moves
+            .iter()
+            .find(|mv| mv.from == Square::E2 && mv.to == Square::E4)
+            .unwrap();
+        board.make_move(e4);
+
+        let fen = board.to_fen();
+        assert_eq!(
+            fen,
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"
+        );
+        assert_eq!(
+            Board::from_fen(&fen).unwrap().zobrist_hash,
+            board.zobrist_hash
+        );
+    }
+
+    #[test]
+    fn test_lenient_fen() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R b Qk -";
+
+        assert_eq!(Board::from_fen(fen).err(), Some(FenError::FieldCount(4)));
+
+        let board = Board::from_fen_lenient(fen).unwrap();
+        assert_eq!(board.to_fen(), "r3k2r/8/8/8/8/8/8/R3K2R b Qk - 0 1");
+        assert_eq!(
+            Board::from_fen_lenient(&board.to_fen()).unwrap().to_fen(),
+            board.to_fen()
+        );
+        assert_eq!(
+            Board::from_fen_lenient("8/8/8/8/8/8/8/K1k5 w").err(),
+            Some(FenError::FieldCount(2))
+        );
+
+        // Rights without their king and rook at home are dropped.
+        let board = Board::from_fen_lenient("1r2k2r/8/8/8/8/8/8/4K3 w KQkq - 0 1").unwrap();
+        assert_eq!(board.to_fen(), "1r2k2r/8/8/8/8/8/8/4K3 w k - 0 1");
+    }
+
+    #[test]
+    fn test_rejects_malformed_fen() {
+        let cases = [
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP w KQkq - 0 1",
+                FenError::RankCount(7),
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNRR w KQkq - 0 1",
+                FenError::RankLength {
+                    rank: Rank::First,
+                    index: 35,
+                },
+            ),
+            (
+                "rnbqkbnr/ppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                FenError::RankLength {
+                    rank: Rank::Seventh,
+                    index: 9,
+                },
+            ),
+            (
+                "rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                FenError::InvalidPiece {
+                    index: 18,
+                    found: '9',
+                },
+            ),
+            (
+                "rnbqkbnr/pppxpppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                FenError::InvalidPiece {
+                    index: 12,
+                    found: 'x',
+                },
+            ),
+            (
+                "4k3/8/8/8/8/8/8/8 w - - 0 1",
+                FenError::KingCount {
+                    color: Color::White,
+                    count: 0,
+                },
+            ),
+            (
+                "4k3/8/8/8/8/8/8/3KK3 w - - 0 1",
+                FenError::KingCount {
+                    color: Color::White,
+                    count: 2,
+                },
+            ),
+            (
+                "P3k3/8/8/8/8/8/8/4K3 w - - 0 1",
+                FenError::PawnOnBackRank(Square::A8),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K2p b - - 0 1",
+                FenError::PawnOnBackRank(Square::H1),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 x - - 0 1",
+                FenError::InvalidSideToMove { index: 20 },
+            ),
+            (
+                "r3k2r/8/8/8/8/8/8/R3K2R w KK - 0 1",
+                FenError::InvalidCastling {
+                    index: 27,
+                    found: 'K',
+                },
+            ),
+            (
+                "r3k2r/8/8/8/8/8/8/R3K2R w -K - 0 1",
+                FenError::InvalidCastling {
+                    index: 26,
+                    found: '-',
+                },
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w K - 0 1",
+                FenError::ImpossibleCastling {
+                    index: 22,
+                    found: 'K',
+                },
+            ),
+            (
+                "r3k2r/8/8/8/8/8/8/R4K1R w K - 0 1",
+                FenError::ImpossibleCastling {
+                    index: 26,
+                    found: 'K',
+                },
+            ),
+            (
+                "1r2k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1",
+                FenError::ImpossibleCastling {
+                    index: 30,
+                    found: 'q',
+                },
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - e9 0 1",
+                FenError::InvalidEnPassant { index: 24 },
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - e6 0 1",
+                FenError::ImplausibleEnPassant(Square::E6),
+            ),
+            (
+                "4k3/8/8/4p3/8/8/8/4K3 w - e3 0 1",
+                FenError::ImplausibleEnPassant(Square::E3),
+            ),
+            (
+                "4k3/4p3/8/4p3/8/8/8/4K3 w - e6 0 1",
+                FenError::ImplausibleEnPassant(Square::E6),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - - x 1",
+                FenError::InvalidHalfmoveClock { index: 26 },
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - - 0 0",
+                FenError::InvalidFullmoveNumber { index: 28 },
+            ),
+        ];
+
+        for (fen, error) in cases {
+            assert_eq!(Board::from_fen(fen).err(), Some(error), "{}", fen);
+        }
+
+        assert!(Board::from_fen("4k3/8/8/4p3/8/8/8/4K3 w - e6 0 1").is_ok());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::board::{Board, Color};
+use crate::board::{Board, Color, Piece};
 
 #[derive(Debug, PartialEq)]
 pub enum GameResult {
@@ -15,11 +15,7 @@ impl Board {
     let legal = self.generate_legal_moves();
 
     if legal.is_empty() {
-      let king_sq = if self.turn == Color::White {
-        self.white_king.next_square()
-      } else {
-        self.black_king.next_square()
-      };
+      let king_sq = self.pieces_of(self.turn, Piece::King).next_square();
 
       if let Some(king_sq) = king_sq {
         if self.is_square_attacked(king_sq, self.turn.opposite()) {
@@ -38,6 +34,138 @@ impl Board {
       return GameResult::DrawInsufficientMaterial;
     }
 
+    if self.repetition_count() >= 2 {
+      return GameResult::DrawRepetition;
+    }
+
     GameResult::Ongoing
   }
-}
\ No newline at end of file
+
+  /// How many times the current position occurred before.
+  pub fn repetition_count(&self) -> usize {
+    self.earlier_occurrences().count()
+  }
+
+  /// Whether the search should score the position as a draw. A position
+  /// reached again after the search root (`root_len` is the history length
+  /// there) counts at its first repetition, since the side that repeated
+  /// could repeat again. Positions from the game before the root only
+  /// count once they occurred twice, as that is a real threefold.
+  pub fn is_repetition(&self, root_len: usize) -> bool {
+    self.repetition_count() >= 2
+      || self.earlier_occurrences().next().is_some_and(|idx| idx >= root_len)
+  }
+
+  /// History indices of the earlier occurrences of the current position,
+  /// latest first. Only positions since the last capture or pawn move can
+  /// repeat, so the scan stops at the halfmove clock and only looks at
+  /// positions with the same side to move.
+  fn earlier_occurrences(&self) -> impl Iterator<Item = usize> + '_ {
+    let window = (self.halfmove_clock as usize).min(self.history.len());
+
+    self.history
+      .iter()
+      .enumerate()
+      .rev()
+      .take(window)
+      .skip(1)
+      .step_by(2)
+      .filter(|(_, undo)| undo.hash == self.zobrist_hash)
+      .map(|(idx, _)| idx)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn play(board: &mut Board, moves: &[&str]) {
+    for text in moves {
+      let mv = board
+        .generate_legal_moves()
+        .into_iter()
+        .find(|mv| format!("{:?}{:?}", mv.from, mv.to).to_lowercase() == *text)
+        .unwrap();
+      board.make_move(&mv);
+    }
+  }
+
+  #[test]
+  fn test_threefold_repetition() {
+    let mut board = Board::default();
+    let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+
+    play(&mut board, &shuffle);
+    assert_eq!(board.repetition_count(), 1);
+    assert!(board.is_repetition(0));
+    assert_eq!(board.game_result(), GameResult::Ongoing);
+
+    play(&mut board, &shuffle);
+    assert_eq!(board.repetition_count(), 2);
+    assert_eq!(board.game_result(), GameResult::DrawRepetition);
+  }
+
+  #[test]
+  fn test_repetition_window_stops_at_irreversible_move() {
+    let mut board = Board::default();
+    play(&mut board, &["g1f3", "g8f6", "f3g1", "f6g8"]);
+    assert!(board.is_repetition(0));
+
+    // The pawn move resets the clock, so earlier positions can never recur.
+    play(&mut board, &["e2e3", "e7e6"]);
+    assert_eq!(board.halfmove_clock, 0);
+    assert!(!board.is_repetition(0));
+
+    play(&mut board, &["g1f3", "g8f6", "f3g1", "f6g8"]);
+    assert_eq!(board.repetition_count(), 1);
+  }
+
+  #[test]
+  fn test_repetition_before_root_needs_two_occurrences() {
+    let mut board = Board::default();
+    let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+
+    play(&mut board, &shuffle);
+    let root_len = board.history.len();
+    assert!(!board.is_repetition(root_len));
+
+    // Returning to the root position counts straight away.
+    play(&mut board, &["g1f3"]);
+    let root_len = board.history.len();
+    play(&mut board, &["g8f6", "f3g1", "f6g8", "g1f3"]);
+    assert!(board.is_repetition(root_len));
+
+    // Two occurrences before the root are a real threefold.
+    play(&mut board, &["g8f6", "f3g1", "f6g8"]);
+    assert!(board.is_repetition(board.history.len()));
+  }
+
+  #[test]
+  fn test_history_records_previous_hashes() {
+    let mut board = Board::default();
+    let mv = board.generate_legal_moves()[0];
+
+    board.make_move(&mv);
+    assert_eq!(board.history.len(), 1);
+    assert_eq!(board.history[0].hash, Board::default().zobrist_hash);
+
+    board.unmake_move(&mv);
+    assert!(board.history.is_empty());
+  }
+
+  #[test]
+  fn test_insufficient_material_result() {
+    for fen in ["4k3/8/8/8/8/8/8/3QK3 w - - 0 1", "4k3/8/8/8/8/8/8/3RK3 b - - 0 1"] {
+      assert_eq!(Board::from_fen(fen).unwrap().game_result(), GameResult::Ongoing, "{}", fen);
+    }
+
+    for fen in ["4k3/8/8/8/8/8/8/4K3 w - - 0 1", "4k3/8/8/8/8/8/8/3BK3 b - - 0 1"] {
+      assert_eq!(
+        Board::from_fen(fen).unwrap().game_result(),
+        GameResult::DrawInsufficientMaterial,
+        "{}",
+        fen
+      );
+    }
+  }
+}
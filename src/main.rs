@@ -1,21 +1,537 @@
+mod bitboard;
 mod board;
-mod moves;
 mod consts;
-mod utils;
+mod engine;
+mod evaluation;
+mod fen;
+mod game_result;
 mod magic;
-mod precomputed;
-mod perft;
 mod magic_gen;
-mod rng;
-mod bitboard;
-mod magics;
 mod magic_index_gen;
+mod magics;
+mod movepick;
+mod moves;
+mod notation;
+mod pawns;
+mod perft;
+mod pgn;
+mod precomputed;
+mod rng;
+mod see;
 mod sliding_pieces;
-mod game_result;
+mod tt;
+mod utils;
+mod zobrist;
+
+use std::env;
+use std::fs;
+use std::io::{self, BufRead};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 use crate::board::Board;
+use crate::engine::{Engine, SearchInfo, SearchLimits, SearchParams, MATE, MATE_BOUND};
+use crate::evaluation::EvalWeights;
+use crate::moves::Move;
+use crate::perft::{perft_divide, perft_parallel};
+use crate::tt::DEFAULT_HASH_MB;
+
+const ENGINE_NAME: &str = "Better Engine";
+const ENGINE_AUTHOR: &str = "santinzz";
+const MAX_HASH_MB: usize = 4096;
+const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+/// Per-thread hash table size of the `perft` subcommand.
+const PERFT_HASH_MB: usize = 64;
 
 fn main() {
-  println!("hola mundo");
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    match args.first().map(String::as_str) {
+        Some("perft") => {
+            if let Err(err) = run_perft(&args[1..]) {
+                eprintln!("{}", err);
+                eprintln!("usage: better-engine perft <depth> [fen]");
+                std::process::exit(1);
+            }
+        }
+        Some("pgn") => {
+            if let Err(err) = run_pgn(&args[1..]) {
+                eprintln!("{}", err);
+                eprintln!("usage: better-engine pgn <file> [--fen]");
+                std::process::exit(1);
+            }
+        }
+        _ => run_uci_loop(),
+    }
+}
+
+/// `pgn <file> [--fen]`: writes the games back out in export format, or
+/// with `--fen` prints the FEN of every position along their main lines.
+fn run_pgn(args: &[String]) -> Result<(), String> {
+    let (path, fens) = match args {
+        [path] => (path, false),
+        [path, flag] if flag == "--fen" => (path, true),
+        _ => return Err("pgn needs a file".to_string()),
+    };
+
+    let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let games = pgn::read_games(&text).map_err(|err| err.to_string())?;
+
+    for game in &games {
+        if fens {
+            let mut board = game.start.clone();
+            println!("{}", board.to_fen());
+            for node in game.mainline() {
+                board.make_move(&node.mv);
+                println!("{}", board.to_fen());
+            }
+        } else {
+            println!("{}", game.to_pgn());
+        }
+    }
+
+    Ok(())
+}
+
+/// `perft <depth> [fen]`: prints the node count below each root move in the
+/// format of Stockfish's `go perft`, followed by the total and the speed.
+fn run_perft(args: &[String]) -> Result<(), String> {
+    let (depth, board) = parse_perft_args(args)?;
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+
+    let start = Instant::now();
+    let divide = perft_parallel(&board, depth, threads, PERFT_HASH_MB);
+    print_divide(&divide, start.elapsed());
+
+    Ok(())
+}
+
+fn print_divide(divide: &[(Move, u64)], elapsed: Duration) {
+    for (mv, nodes) in divide {
+        println!("{}: {}", mv.to_uci(), nodes);
+    }
+
+    let nodes: u64 = divide.iter().map(|&(_, nodes)| nodes).sum();
+    let millis = elapsed.as_millis() as u64;
+
+    println!();
+    println!("Nodes searched: {}", nodes);
+    println!("Time: {} ms, nps: {}", millis, nodes * 1000 / millis.max(1));
+}
+
+fn parse_perft_args(args: &[String]) -> Result<(u32, Board), String> {
+    let depth = match args.first().map(|d| d.parse::<u32>()) {
+        Some(Ok(depth)) if depth > 0 => depth,
+        _ => return Err("perft needs a positive depth".to_string()),
+    };
+
+    let fen = match &args[1..] {
+        [] => START_FEN.to_string(),
+        rest => rest.join(" "),
+    };
+
+    Ok((depth, Board::from_fen(&fen).map_err(|err| err.to_string())?))
+}
+
+/// A search running in the background, started by `go` and finished by
+/// `stop`, `ponderhit` or reaching its limits. The engine is moved into the
+/// thread and handed back when it is joined.
+struct SearchThread {
+    stop: Arc<AtomicBool>,
+    pondering: Arc<AtomicBool>,
+    handle: JoinHandle<Engine>,
+}
+
+impl SearchThread {
+    fn start(mut engine: Engine, board: &Board, limits: SearchLimits) -> SearchThread {
+        let stop = engine.stop_handle();
+        let pondering = engine.ponder_handle();
+        stop.store(false, Ordering::Relaxed);
+        pondering.store(limits.ponder, Ordering::Relaxed);
+
+        let mut board = board.clone();
+
+        let handle = {
+            let stop = Arc::clone(&stop);
+            let pondering = Arc::clone(&pondering);
+
+            thread::spawn(move || {
+                let result = engine.search(&mut board, &limits);
+
+                // The protocol forbids sending bestmove while pondering or in
+                // an infinite search, so hold it back until the GUI says so.
+                while (limits.infinite || pondering.load(Ordering::Relaxed))
+                    && !stop.load(Ordering::Relaxed)
+                {
+                    thread::sleep(Duration::from_millis(1));
+                }
+
+                match (result.best_move, result.pv.get(1)) {
+                    (Some(best), Some(reply)) => {
+                        println!("bestmove {} ponder {}", best.to_uci(), reply.to_uci())
+                    }
+                    (Some(best), None) => println!("bestmove {}", best.to_uci()),
+                    (None, _) => println!("bestmove 0000"),
+                }
+
+                engine
+            })
+        };
+
+        SearchThread {
+            stop,
+            pondering,
+            handle,
+        }
+    }
+
+    fn ponderhit(&self) {
+        self.pondering.store(false, Ordering::Relaxed);
+    }
+
+    fn finish(self) -> Engine {
+        self.stop.store(true, Ordering::Relaxed);
+        self.handle.join().expect("search thread panicked")
+    }
+}
+
+/// Stops the running search, if any, and gives its engine back.
+fn stop_search(search: &mut Option<SearchThread>, engine: &mut Option<Engine>) {
+    if let Some(running) = search.take() {
+        *engine = Some(running.finish());
+    }
+}
+
+fn new_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_info_callback(print_info);
+    engine
+}
+
+fn print_info(info: &SearchInfo) {
+    let score = if info.score.abs() >= MATE_BOUND {
+        let moves = (MATE - info.score.abs() + 1) / 2;
+        format!("mate {}", if info.score > 0 { moves } else { -moves })
+    } else {
+        format!("cp {}", info.score)
+    };
+
+    let millis = info.time.as_millis() as u64;
+    let nps = info.nodes * 1000 / millis.max(1);
+    let pv: Vec<String> = info.pv.iter().map(|mv| mv.to_uci()).collect();
+
+    println!(
+        "info depth {} score {} nodes {} nps {} hashfull {} time {} pv {}",
+        info.depth,
+        score,
+        info.nodes,
+        nps,
+        info.hashfull,
+        millis,
+        pv.join(" ")
+    );
+}
+
+fn run_uci_loop() {
+    let mut board = Board::default();
+    let mut engine = Some(new_engine());
+    let mut search: Option<SearchThread> = None;
+
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else { break };
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+
+        let Some(&command) = tokens.first() else {
+            continue;
+        };
+
+        match command {
+            "uci" => {
+                println!("id name {}", ENGINE_NAME);
+                println!("id author {}", ENGINE_AUTHOR);
+                println!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    DEFAULT_HASH_MB, MAX_HASH_MB
+                );
+                println!("option name EvalFile type string default <empty>");
+                println!("option name Ponder type check default false");
+                println!("option name QuiescenceChecks type check default false");
+                let params = SearchParams::default();
+                for (name, default) in [
+                    ("NullMove", params.null_move),
+                    ("NullMoveVerification", params.null_move_verification),
+                    ("LateMoveReductions", params.late_move_reductions),
+                    ("ReverseFutility", params.reverse_futility),
+                    ("Futility", params.futility),
+                    ("LateMovePruning", params.late_move_pruning),
+                    ("PVS", params.pvs),
+                    ("CheckExtension", params.check_extension),
+                    ("SingularExtension", params.singular_extension),
+                    ("PassedPawnExtension", params.passed_pawn_extension),
+                    ("MateDistancePruning", params.mate_distance_pruning),
+                ] {
+                    println!("option name {} type check default {}", name, default);
+                }
+                println!("uciok");
+            }
+            "isready" => println!("readyok"),
+            "ucinewgame" => {
+                stop_search(&mut search, &mut engine);
+                board = Board::default();
+                if let Some(engine) = engine.as_mut() {
+                    engine.tt.clear();
+                    engine.history.clear();
+                }
+            }
+            "setoption" => {
+                stop_search(&mut search, &mut engine);
+                if let Some(engine) = engine.as_mut() {
+                    set_option(engine, &tokens[1..]);
+                }
+            }
+            "position" => match parse_position(&tokens[1..]) {
+                Ok(new_board) => board = new_board,
+                Err(err) => println!("info string {}", err),
+            },
+            // Same output as the `perft` subcommand, on the current position.
+            "go" if tokens.get(1) == Some(&"perft") => {
+                match tokens.get(2).and_then(|d| d.parse::<u32>().ok()) {
+                    Some(depth) => {
+                        let start = Instant::now();
+                        let divide = perft_divide(&mut board, depth);
+                        print_divide(&divide, start.elapsed());
+                    }
+                    None => println!("info string go perft needs a depth"),
+                }
+            }
+            "go" => {
+                stop_search(&mut search, &mut engine);
+                let engine = engine.take().expect("engine is idle after stop_search");
+                search = Some(SearchThread::start(engine, &board, parse_go(&tokens[1..])));
+            }
+            "stop" => stop_search(&mut search, &mut engine),
+            "ponderhit" => {
+                if let Some(running) = &search {
+                    running.ponderhit();
+                }
+            }
+            "d" => board.print(),
+            "quit" => break,
+            _ => println!("info string unknown command: {}", command),
+        }
+    }
+
+    stop_search(&mut search, &mut engine);
+}
+
+/// Parses the arguments of `position [startpos | fen <fen>] [moves <m1> ...]`.
+/// The FEN may leave out the clocks.
+fn parse_position(args: &[&str]) -> Result<Board, String> {
+    let moves_idx = args.iter().position(|&t| t == "moves");
+    let (setup, moves) = match moves_idx {
+        Some(idx) => (&args[..idx], &args[idx + 1..]),
+        None => (args, &[][..]),
+    };
+
+    let mut board = match setup.first() {
+        Some(&"startpos") => Board::default(),
+        Some(&"fen") => {
+            Board::from_fen_lenient(&setup[1..].join(" ")).map_err(|err| err.to_string())?
+        }
+        _ => return Err("position must be followed by startpos or fen".to_string()),
+    };
+
+    for text in moves {
+        let mv = board.parse_uci_move(text).map_err(|err| err.to_string())?;
+        board.make_move(&mv);
+    }
+
+    Ok(board)
+}
+
+/// Applies `setoption name <name> [value <value>]`. Option names are
+/// case-insensitive and may contain spaces.
+fn set_option(engine: &mut Engine, args: &[&str]) {
+    let value_idx = args
+        .iter()
+        .position(|&t| t == "value")
+        .unwrap_or(args.len());
+    let name = args.get(1..value_idx).unwrap_or_default().join(" ");
+    let value = args.get(value_idx + 1..).unwrap_or_default().join(" ");
+
+    match name.to_lowercase().as_str() {
+        // Only advertised so GUIs send `go ponder`.
+        "ponder" => {}
+        "evalfile" => match load_weights(&value) {
+            Ok(weights) => engine.evaluator.set_weights(weights),
+            Err(err) => println!("info string could not load {}: {}", value, err),
+        },
+        "hash" => match value.parse::<usize>() {
+            Ok(mb) => engine.tt.resize(mb.clamp(1, MAX_HASH_MB)),
+            Err(_) => println!("info string invalid hash size: {}", value),
+        },
+        "quiescencechecks" => engine.params.qsearch_checks = value == "true",
+        "nullmove" => engine.params.null_move = value == "true",
+        "nullmoveverification" => engine.params.null_move_verification = value == "true",
+        "latemovereductions" => engine.params.late_move_reductions = value == "true",
+        "reversefutility" => engine.params.reverse_futility = value == "true",
+        "futility" => engine.params.futility = value == "true",
+        "latemovepruning" => engine.params.late_move_pruning = value == "true",
+        "pvs" => engine.params.pvs = value == "true",
+        "checkextension" => engine.params.check_extension = value == "true",
+        "singularextension" => engine.params.singular_extension = value == "true",
+        "passedpawnextension" => engine.params.passed_pawn_extension = value == "true",
+        "matedistancepruning" => engine.params.mate_distance_pruning = value == "true",
+        _ => println!("info string unknown option: {}", name),
+    }
+}
+
+fn load_weights(path: &str) -> Result<EvalWeights, String> {
+    if path.is_empty() || path == "<empty>" {
+        return Ok(EvalWeights::default());
+    }
+
+    fs::read_to_string(path)
+        .map_err(|err| err.to_string())?
+        .parse()
+}
+
+fn parse_go(args: &[&str]) -> SearchLimits {
+    let mut options = SearchLimits::default();
+    let mut iter = args.iter();
+
+    while let Some(&token) = iter.next() {
+        let mut value = || iter.next().and_then(|v| v.parse::<u64>().ok());
+
+        match token {
+            "wtime" => options.wtime = value(),
+            "btime" => options.btime = value(),
+            "winc" => options.winc = value(),
+            "binc" => options.binc = value(),
+            "movestogo" => options.movestogo = value().map(|v| v as u32),
+            "depth" => options.depth = value().map(|v| v as u32),
+            "nodes" => options.nodes = value(),
+            "movetime" => options.movetime = value(),
+            "infinite" => options.infinite = true,
+            "ponder" => options.ponder = true,
+            _ => {}
+        }
+    }
+
+    options
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Piece;
+    use crate::consts::Square;
+
+    #[test]
+    fn test_parse_position_with_moves() {
+        let board = parse_position(&["startpos", "moves", "e2e4", "e7e5", "g1f3"]).unwrap();
+
+        assert_eq!(board.turn, board::Color::Black);
+        assert_eq!(
+            board.piece_on_square(Square::F3),
+            Some((Piece::Knight, board::Color::White))
+        );
+        assert_eq!(
+            board.piece_on_square(Square::E5),
+            Some((Piece::Pawn, board::Color::Black))
+        );
+    }
+
+    #[test]
+    fn test_parse_position_rejects_illegal_move() {
+        assert!(parse_position(&["startpos", "moves", "e2e5"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_position_accepts_fen_without_clocks() {
+        let board = parse_position(&[
+            "fen",
+            "4k3/8/8/8/8/8/4P3/4K3",
+            "b",
+            "-",
+            "-",
+            "moves",
+            "e8d7",
+        ])
+        .unwrap();
+
+        assert_eq!(board.to_fen(), "8/3k4/8/8/8/8/4P3/4K3 w - - 1 2");
+        assert!(parse_position(&["fen", "4k3/8/8/8/8/8/4P3/4K3", "b"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_position_drops_impossible_castling() {
+        let args = [
+            "fen",
+            "4k3/8/8/8/8/8/8/4K3",
+            "w",
+            "KQ",
+            "-",
+            "0",
+            "1",
+            "moves",
+            "e1g1",
+        ];
+
+        assert!(parse_position(&args).is_err());
+    }
+
+    #[test]
+    fn test_set_option() {
+        let mut engine = Engine::new();
+
+        set_option(&mut engine, &["name", "QuiescenceChecks", "value", "true"]);
+        assert!(engine.params.qsearch_checks);
+
+        set_option(&mut engine, &["name", "QuiescenceChecks", "value", "false"]);
+        assert!(!engine.params.qsearch_checks);
+    }
+
+    #[test]
+    fn test_parse_go() {
+        let options = parse_go(&[
+            "wtime",
+            "300000",
+            "btime",
+            "290000",
+            "winc",
+            "2000",
+            "movestogo",
+            "40",
+        ]);
+
+        assert_eq!(options.wtime, Some(300000));
+        assert_eq!(options.btime, Some(290000));
+        assert_eq!(options.winc, Some(2000));
+        assert_eq!(options.binc, None);
+        assert_eq!(options.movestogo, Some(40));
+        assert!(!options.infinite);
+    }
+
+    #[test]
+    fn test_parse_perft_args() {
+        let args = |text: &str| {
+            text.split_whitespace()
+                .map(String::from)
+                .collect::<Vec<_>>()
+        };
+
+        let (depth, board) = parse_perft_args(&args("5")).unwrap();
+        assert_eq!(depth, 5);
+        assert_eq!(board.zobrist_hash, Board::default().zobrist_hash);
+
+        let (_, board) = parse_perft_args(&args("3 8/8/8/8/8/8/8/k6K b - - 0 1")).unwrap();
+        assert_eq!(board.turn, board::Color::Black);
+
+        assert!(parse_perft_args(&args("")).is_err());
+        assert!(parse_perft_args(&args("0")).is_err());
+        assert!(parse_perft_args(&args("2 not-a-fen")).is_err());
+    }
+}
@@ -0,0 +1,367 @@
+use crate::{
+    board::{Board, Color},
+    engine::{piece_value, MAX_PLY},
+    moves::{Move, MoveList, PackedMove, MAX_MOVES},
+};
+
+/// History scores stay within this bound in either direction.
+const HISTORY_MAX: i32 = 16_384;
+
+/// Most valuable victim first, least valuable attacker as tie-break.
+/// Promotions count as capturing the piece promoted to.
+pub fn mvv_lva(mv: &Move) -> i32 {
+    let gain = mv.captured_piece.map_or(0, piece_value) + mv.promotion.map_or(0, piece_value);
+    gain * 16 - piece_value(mv.piece) / 16
+}
+
+/// What the search learned about quiet moves: killers per ply, a butterfly
+/// history table and countermoves.
+pub struct MoveHistory {
+    /// Two quiet moves per ply that recently caused a cutoff there.
+    killers: [[PackedMove; 2]; MAX_PLY + 1],
+    /// Indexed by side to move, from and to square.
+    butterfly: [[[i32; 64]; 64]; 2],
+    /// The quiet move that last refuted a move, by the refuted move's from
+    /// and to square.
+    countermoves: [[PackedMove; 64]; 64],
+}
+
+impl Default for MoveHistory {
+    fn default() -> Self {
+        MoveHistory {
+            killers: [[PackedMove::NULL; 2]; MAX_PLY + 1],
+            butterfly: [[[0; 64]; 64]; 2],
+            countermoves: [[PackedMove::NULL; 64]; 64],
+        }
+    }
+}
+
+impl MoveHistory {
+    pub fn clear(&mut self) {
+        *self = MoveHistory::default();
+    }
+
+    /// Killers are only good for the position they came from; the history
+    /// is halved so it keeps some of what it learned.
+    pub fn new_search(&mut self) {
+        self.killers = [[PackedMove::NULL; 2]; MAX_PLY + 1];
+        for score in self.butterfly.iter_mut().flatten().flatten() {
+            *score /= 2;
+        }
+    }
+
+    /// The killers of `ply` and the countermove to `prev`, for `MovePicker`.
+    pub fn refutations(&self, ply: usize, prev: Option<Move>) -> [PackedMove; 3] {
+        let killers = self.killers[ply.min(MAX_PLY)];
+        let countermove = prev.map_or(PackedMove::NULL, |prev| {
+            self.countermoves[prev.from as usize][prev.to as usize]
+        });
+
+        [killers[0], killers[1], countermove]
+    }
+
+    pub fn score(&self, color: Color, mv: &Move) -> i32 {
+        self.butterfly[color as usize][mv.from as usize][mv.to as usize]
+    }
+
+    /// Rewards the quiet move `best` that failed high at `ply` and punishes
+    /// the quiet moves in `tried` that were searched before it.
+    pub fn record_cutoff(
+        &mut self,
+        color: Color,
+        best: &Move,
+        tried: &[Move],
+        ply: usize,
+        prev: Option<Move>,
+        depth: u32,
+    ) {
+        let bonus = (depth * depth).min(400) as i32;
+
+        self.add(color, best, bonus);
+        for mv in tried {
+            self.add(color, mv, -bonus);
+        }
+
+        let packed = PackedMove::from(*best);
+        let killers = &mut self.killers[ply.min(MAX_PLY)];
+        if killers[0] != packed {
+            killers[1] = killers[0];
+            killers[0] = packed;
+        }
+
+        if let Some(prev) = prev {
+            self.countermoves[prev.from as usize][prev.to as usize] = packed;
+        }
+    }
+
+    /// Moves the score towards the bound by `bonus`, less the closer it is.
+    fn add(&mut self, color: Color, mv: &Move, bonus: i32) {
+        let score = &mut self.butterfly[color as usize][mv.from as usize][mv.to as usize];
+        *score += bonus - *score * bonus.abs() / HISTORY_MAX;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    HashMove,
+    GenerateTactical,
+    Tactical,
+    Refutations,
+    GenerateQuiets,
+    Quiets,
+    Done,
+}
+
+/// Hands out the legal moves of a position one at a time, best guesses
+/// first: the hash move, captures and promotions by MVV-LVA, the killers
+/// and the countermove, then the other quiet moves by history. Each group is
+/// only generated once the ones before it are used up, so a cutoff early on
+/// saves generating the rest.
+pub struct MovePicker {
+    stage: Stage,
+    hash_move: Option<Move>,
+    refutations: [PackedMove; 3],
+    /// The refutations that were legal and handed out.
+    played_refutations: [Option<Move>; 3],
+    next_refutation: usize,
+    moves: MoveList,
+    scores: [i32; MAX_MOVES],
+    next: usize,
+}
+
+impl MovePicker {
+    /// `hash_move` and `refutations` may come from other positions; they
+    /// are checked for legality before they are handed out.
+    pub fn new(hash_move: Option<Move>, refutations: [PackedMove; 3]) -> MovePicker {
+        MovePicker {
+            stage: Stage::HashMove,
+            hash_move,
+            refutations,
+            played_refutations: [None; 3],
+            next_refutation: 0,
+            moves: MoveList::new(),
+            scores: [0; MAX_MOVES],
+            next: 0,
+        }
+    }
+
+    pub fn next(&mut self, board: &Board, history: &MoveHistory) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::HashMove => {
+                    self.stage = Stage::GenerateTactical;
+                    self.hash_move = self.hash_move.filter(|mv| board.is_legal(mv));
+
+                    if self.hash_move.is_some() {
+                        return self.hash_move;
+                    }
+                }
+                Stage::GenerateTactical => {
+                    board.generate_tactical_moves_into(&mut self.moves, false);
+                    for (score, mv) in self.scores.iter_mut().zip(self.moves.iter()) {
+                        *score = mvv_lva(mv);
+                    }
+
+                    self.next = 0;
+                    self.stage = Stage::Tactical;
+                }
+                Stage::Tactical => match self.pick_best() {
+                    Some(mv) if Some(mv) == self.hash_move => {}
+                    Some(mv) => return Some(mv),
+                    None => self.stage = Stage::Refutations,
+                },
+                Stage::Refutations => {
+                    let Some(&packed) = self.refutations.get(self.next_refutation) else {
+                        self.stage = Stage::GenerateQuiets;
+                        continue;
+                    };
+                    let idx = self.next_refutation;
+                    self.next_refutation += 1;
+
+                    if packed.is_null() || self.refutations[..idx].contains(&packed) {
+                        continue;
+                    }
+
+                    let mv = board.unpack_move(packed).filter(|mv| {
+                        mv.is_quiet() && Some(*mv) != self.hash_move && board.is_legal(mv)
+                    });
+                    if mv.is_some() {
+                        self.played_refutations[idx] = mv;
+                        return mv;
+                    }
+                }
+                Stage::GenerateQuiets => {
+                    board.generate_quiet_moves_into(&mut self.moves);
+                    for (score, mv) in self.scores.iter_mut().zip(self.moves.iter()) {
+                        *score = history.score(board.turn, mv);
+                    }
+
+                    self.next = 0;
+                    self.stage = Stage::Quiets;
+                }
+                Stage::Quiets => match self.pick_best() {
+                    Some(mv)
+                        if Some(mv) == self.hash_move
+                            || self.played_refutations.contains(&Some(mv)) => {}
+                    Some(mv) => return Some(mv),
+                    None => self.stage = Stage::Done,
+                },
+                Stage::Done => return None,
+            }
+        }
+    }
+
+    /// Swaps the best scored move left into place and returns it.
+    fn pick_best(&mut self) -> Option<Move> {
+        let len = self.moves.len();
+        if self.next >= len {
+            return None;
+        }
+
+        let best = (self.next + 1..len).fold(self.next, |best, idx| {
+            if self.scores[idx] > self.scores[best] {
+                idx
+            } else {
+                best
+            }
+        });
+
+        self.moves.swap(self.next, best);
+        self.scores.swap(self.next, best);
+        self.next += 1;
+
+        Some(self.moves[self.next - 1])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    fn picked(board: &Board, picker: &mut MovePicker, history: &MoveHistory) -> Vec<Move> {
+        std::iter::from_fn(|| picker.next(board, history)).collect()
+    }
+
+    fn find(board: &Board, uci: &str) -> Move {
+        board.parse_uci_move(uci).unwrap()
+    }
+
+    #[test]
+    fn test_picks_every_legal_move_once() {
+        let foreign =
+            Board::from_fen("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8").unwrap();
+        let history = MoveHistory::default();
+
+        for fen in [
+            KIWIPETE,
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1",
+            "4k3/8/8/8/1b6/8/8/RNBQK1NR w - - 0 1",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            let legal = board.generate_legal_moves();
+
+            // Hash moves and killers from this and another position, legal
+            // or not, and repeated.
+            let mut candidates: Vec<Move> = legal.iter().copied().step_by(3).collect();
+            candidates.extend(foreign.generate_legal_moves().iter().step_by(2));
+
+            for (i, &hash_move) in candidates.iter().enumerate() {
+                let killer = PackedMove::from(candidates[(i + 1) % candidates.len()]);
+                let refutations = [killer, PackedMove::from(hash_move), killer];
+                let mut picker = MovePicker::new(Some(hash_move), refutations);
+
+                let mut moves = picked(&board, &mut picker, &history);
+                let mut expected: Vec<Move> = legal.iter().copied().collect();
+                let key = |mv: &Move| mv.to_uci();
+                moves.sort_by_key(key);
+                expected.sort_by_key(key);
+                assert_eq!(moves, expected, "{}", fen);
+            }
+        }
+    }
+
+    #[test]
+    fn test_pick_order() {
+        let board = Board::from_fen(KIWIPETE).unwrap();
+        let mut history = MoveHistory::default();
+        history.add(Color::White, &find(&board, "a2a3"), 300);
+        history.add(Color::White, &find(&board, "g2g3"), 200);
+
+        let hash_move = find(&board, "e2a6");
+        let killer = find(&board, "e1g1");
+        let refutations = [
+            PackedMove::from(killer),
+            PackedMove::NULL,
+            PackedMove::from(hash_move),
+        ];
+        let mut picker = MovePicker::new(Some(hash_move), refutations);
+        let moves = picked(&board, &mut picker, &history);
+
+        assert_eq!(moves[0], hash_move);
+        // The other seven captures, by MVV-LVA.
+        assert_eq!(moves[1], find(&board, "f3f6"));
+        assert!(moves[1..8].iter().all(|mv| mv.captured_piece.is_some()));
+        assert!(moves[1..8]
+            .windows(2)
+            .all(|pair| mvv_lva(&pair[0]) >= mvv_lva(&pair[1])));
+        assert_eq!(moves[7], find(&board, "f3h3"));
+        assert_eq!(moves[8], killer);
+        assert_eq!(moves[9], find(&board, "a2a3"));
+        assert_eq!(moves[10], find(&board, "g2g3"));
+        assert!(moves[8..].iter().all(Move::is_quiet));
+    }
+
+    #[test]
+    fn test_quiets_are_generated_lazily() {
+        let board = Board::from_fen(KIWIPETE).unwrap();
+        let history = MoveHistory::default();
+        let mut picker = MovePicker::new(Some(find(&board, "e1g1")), [PackedMove::NULL; 3]);
+
+        assert_eq!(picker.next(&board, &history), Some(find(&board, "e1g1")));
+        assert!(picker.moves.is_empty());
+
+        assert_eq!(picker.next(&board, &history), Some(find(&board, "e2a6")));
+        assert_eq!(picker.stage, Stage::Tactical);
+    }
+
+    #[test]
+    fn test_record_cutoff() {
+        let board = Board::default();
+        let mut history = MoveHistory::default();
+        let e4 = find(&board, "e2e4");
+        let d4 = find(&board, "d2d4");
+        let nf3 = find(&board, "g1f3");
+        let prev = Some(find(&Board::from_fen(KIWIPETE).unwrap(), "e5f7"));
+
+        history.record_cutoff(Color::White, &e4, &[d4], 3, prev, 4);
+        assert_eq!(history.score(Color::White, &e4), 16);
+        assert_eq!(history.score(Color::White, &d4), -16);
+        assert_eq!(history.score(Color::Black, &e4), 0);
+        assert_eq!(
+            history.refutations(3, prev),
+            [e4.into(), PackedMove::NULL, e4.into()]
+        );
+
+        // The newest killer goes first and the same move is not stored twice.
+        history.record_cutoff(Color::White, &nf3, &[], 3, None, 4);
+        history.record_cutoff(Color::White, &nf3, &[], 3, None, 4);
+        assert_eq!(
+            history.refutations(3, None),
+            [nf3.into(), e4.into(), PackedMove::NULL]
+        );
+
+        // Scores saturate instead of overflowing.
+        for _ in 0..1000 {
+            history.record_cutoff(Color::White, &e4, &[], 3, None, 20);
+        }
+        assert!(history.score(Color::White, &e4) <= HISTORY_MAX);
+
+        history.new_search();
+        assert_eq!(history.refutations(3, None), [PackedMove::NULL; 3]);
+        assert!(history.score(Color::White, &e4) <= HISTORY_MAX / 2);
+        assert_eq!(history.refutations(0, prev)[2], e4.into());
+    }
+}
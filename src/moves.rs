@@ -1,14 +1,17 @@
-use std::cell::RefCell;
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+};
 
 use crate::{
     bitboard::BitBoard,
     board::{Board, Color, Piece},
     consts::{
-        File, Rank, Square, B_KINGSIDE_RIGHTS, B_QUEENSIDE_RIGHTS, DIRECTION_OFFSETS, KING_ATTACKS,
-        KING_MOVES, KNIGHT_MOVES, PAWN_ATTACKS, W_KINGSIDE_RIGHTS, W_QUEENSIDE_RIGHTS,
+        Rank, Square, BETWEEN_MASKS, B_KINGSIDE_RIGHTS, B_QUEENSIDE_RIGHTS, KING_ATTACKS,
+        KNIGHT_ATTACKS, LINE_MASKS, PAWN_ATTACKS, W_KINGSIDE_RIGHTS, W_QUEENSIDE_RIGHTS,
     },
-    precomputed::NumSquaresToTheEdge,
     sliding_pieces::{get_bishop_moves, get_queen_moves, get_rook_moves},
+    utils::piece_attacks,
 };
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -32,104 +35,337 @@ pub struct Move {
     pub flags: Flags, // e.g., 0x1 for double pawn push, 0x2 for en passant, 0x4 for castling
 }
 
-impl Board {
-    pub fn generate_legal_moves_into(&self, moves: &mut Vec<Move>) {
-        moves.clear();
+impl Move {
+    /// Neither a capture nor a promotion.
+    pub fn is_quiet(&self) -> bool {
+        self.captured_piece.is_none() && self.promotion.is_none()
+    }
+}
 
-        self.generate_pawn_moves(moves);
-        self.generate_knight_moves(moves);
-        self.generate_rook_moves(moves);
-        self.generate_bishop_moves(moves);
-        self.generate_queen_moves(moves);
-        self.generate_king_moves(moves);
+/// A move packed into 16 bits: from in bits 0-5, to in bits 6-11 and a
+/// 4-bit flag on top. Which piece moves and what it captures is left to the
+/// board, see `Board::unpack_move`.
+///
+/// Flags: 0 quiet, 1 double pawn push, 2 castling, 4 capture, 5 en passant,
+/// 8-11 promotion to N/B/R/Q and 12-15 the same with a capture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub struct PackedMove(pub u16);
+
+impl PackedMove {
+    /// No move. Never the packing of a real move, since from and to differ.
+    pub const NULL: PackedMove = PackedMove(0);
+
+    const DOUBLE_PAWN_PUSH: u16 = 1;
+    const CASTLING: u16 = 2;
+    const CAPTURE: u16 = 4;
+    const EN_PASSANT: u16 = 5;
+    const PROMOTION: u16 = 8;
+
+    pub fn is_null(self) -> bool {
+        self == PackedMove::NULL
+    }
 
-        moves.retain(|mv| {
-            let mut board_copy = self.clone();
-            board_copy.make_move(mv);
-            !board_copy.is_king_in_check(self.turn.opposite())
-        });
+    pub fn origin(self) -> Square {
+        Square::from_index((self.0 & 0x3f) as u8)
     }
 
-    pub fn generate_legal_moves(&self) -> Vec<Move> {
-        let mut moves = Vec::new();
+    pub fn destination(self) -> Square {
+        Square::from_index((self.0 >> 6 & 0x3f) as u8)
+    }
 
-        self.generate_pawn_moves(&mut moves);
-        self.generate_knight_moves(&mut moves);
-        self.generate_rook_moves(&mut moves);
-        self.generate_queen_moves(&mut moves);
-        self.generate_bishop_moves(&mut moves);
-        self.generate_king_moves(&mut moves);
+    fn flag(self) -> u16 {
+        self.0 >> 12
+    }
 
-        let mut legal_moves: Vec<Move> = Vec::new();
+    pub fn promotion(self) -> Option<Piece> {
+        if self.flag() & Self::PROMOTION == 0 {
+            return None;
+        }
 
-        for &mv in &moves {
-            let mut board_copy = self.clone();
+        Some([Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen][(self.flag() & 3) as usize])
+    }
+}
 
-            board_copy.make_move(&mv);
+impl From<Move> for PackedMove {
+    fn from(mv: Move) -> PackedMove {
+        let promotion = match mv.promotion {
+            Some(Piece::Knight) => Self::PROMOTION,
+            Some(Piece::Bishop) => Self::PROMOTION | 1,
+            Some(Piece::Rook) => Self::PROMOTION | 2,
+            Some(Piece::Queen) => Self::PROMOTION | 3,
+            _ => 0,
+        };
 
-            if !board_copy.is_king_in_check(self.turn.opposite()) {
-                legal_moves.push(mv);
+        let flag = match mv.flags {
+            Flags::DoublePawnPush => Self::DOUBLE_PAWN_PUSH,
+            Flags::Castling => Self::CASTLING,
+            Flags::EnPassant => Self::EN_PASSANT,
+            Flags::Capture | Flags::PromotionCapture => promotion | Self::CAPTURE,
+            Flags::Normal | Flags::Promotion => promotion,
+        };
+
+        PackedMove(mv.from as u16 | (mv.to as u16) << 6 | flag << 12)
+    }
+}
+
+pub const MAX_MOVES: usize = 256;
+
+/// Fixed-capacity move list that lives on the stack. It derefs to a slice,
+/// so it can be indexed, iterated and sorted like one.
+#[derive(Clone)]
+pub struct MoveList {
+    moves: [Move; MAX_MOVES],
+    len: usize,
+}
+
+impl MoveList {
+    /// Fills the unused slots; never visible through the slice.
+    const EMPTY: Move = Move {
+        from: Square::A1,
+        to: Square::A1,
+        piece: Piece::Pawn,
+        captured_piece: None,
+        promotion: None,
+        flags: Flags::Normal,
+    };
+
+    pub fn new() -> MoveList {
+        MoveList {
+            moves: [Self::EMPTY; MAX_MOVES],
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, mv: Move) {
+        self.moves[self.len] = mv;
+        self.len += 1;
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl Default for MoveList {
+    fn default() -> Self {
+        MoveList::new()
+    }
+}
+
+impl Deref for MoveList {
+    type Target = [Move];
+
+    fn deref(&self) -> &[Move] {
+        &self.moves[..self.len]
+    }
+}
+
+impl DerefMut for MoveList {
+    fn deref_mut(&mut self) -> &mut [Move] {
+        &mut self.moves[..self.len]
+    }
+}
+
+impl fmt::Debug for MoveList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a Move;
+    type IntoIter = std::slice::Iter<'a, Move>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for MoveList {
+    type Item = Move;
+    type IntoIter = MoveListIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        MoveListIter {
+            list: self,
+            next: 0,
+        }
+    }
+}
+
+pub struct MoveListIter {
+    list: MoveList,
+    next: usize,
+}
+
+impl Iterator for MoveListIter {
+    type Item = Move;
+
+    fn next(&mut self) -> Option<Move> {
+        let mv = self.list.get(self.next).copied();
+        self.next += 1;
+        mv
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.list.len.saturating_sub(self.next);
+        (left, Some(left))
+    }
+}
+
+/// Check and pin information for the side to move, computed once per
+/// position so the generators can emit legal moves directly.
+pub struct CheckInfo {
+    king: Option<Square>,
+    /// Enemy pieces giving check.
+    pub checkers: BitBoard,
+    /// Our pieces that may only move along the line to our king.
+    pub pinned: BitBoard,
+    /// Where a non-king move has to land: anywhere out of check, on the
+    /// checker or between it and the king in single check, nowhere in
+    /// double check.
+    target: BitBoard,
+}
+
+impl CheckInfo {
+    fn allowed(&self, from: Square) -> BitBoard {
+        match self.king {
+            Some(king) if self.pinned.has(from) => {
+                self.target & BitBoard(LINE_MASKS[king as usize][from as usize])
             }
+            _ => self.target,
         }
+    }
+}
+
+impl Board {
+    pub fn generate_legal_moves_into(&self, moves: &mut MoveList) {
+        moves.clear();
 
-        legal_moves
+        let info = self.check_info();
+
+        // Only the king can get out of a double check.
+        if info.checkers.count() < 2 {
+            self.generate_pawn_moves(moves, &info);
+            self.generate_knight_moves(moves, &info);
+            self.generate_rook_moves(moves, &info);
+            self.generate_bishop_moves(moves, &info);
+            self.generate_queen_moves(moves, &info);
+        }
+        self.generate_king_moves(moves, &info);
     }
 
-    fn generate_pawn_moves(&self, moves: &mut Vec<Move>) {
-        let mut our_pawns = if self.turn == Color::White {
-            self.white_pawns
-        } else {
-            self.black_pawns
+    pub fn check_info(&self) -> CheckInfo {
+        let Some(king) = self.pieces_of(self.turn, Piece::King).next_square() else {
+            return CheckInfo {
+                king: None,
+                checkers: BitBoard::EMPTY,
+                pinned: BitBoard::EMPTY,
+                target: !BitBoard::EMPTY,
+            };
         };
 
-        while our_pawns != BitBoard::EMPTY {
-            let from_sq_idx = our_pawns.0.trailing_zeros() as u8;
-            let rank = Square::from_index(from_sq_idx).rank();
-            let file = Square::from_index(from_sq_idx).file();
-            let pawn_color = self.turn;
+        let checkers = self.checkers();
+        let pinned = self.pinned(self.turn);
 
-            let (forward_dir, start_rank, promotion_rank, capture_dirs) = match pawn_color {
-                Color::White => (8, Rank::Second, Rank::Eighth, [7, 9]),
-                Color::Black => (-8, Rank::Seventh, Rank::First, [-7, -9]),
-            };
+        let target = match checkers.count() {
+            0 => !BitBoard::EMPTY,
+            1 => {
+                let checker = checkers.next_square().unwrap();
+                checkers | BitBoard(BETWEEN_MASKS[king as usize][checker as usize])
+            }
+            _ => BitBoard::EMPTY,
+        };
+
+        CheckInfo {
+            king: Some(king),
+            checkers,
+            pinned,
+            target,
+        }
+    }
+
+    /// Generates the legal moves that change material: captures, en passant
+    /// captures and promotions (including quiet ones). With `include_checks`,
+    /// quiet moves that give direct check are added as well, which lets the
+    /// quiescence search look at checks on its first ply.
+    pub fn generate_tactical_moves_into(&self, moves: &mut MoveList, include_checks: bool) {
+        moves.clear();
+
+        let info = self.check_info();
+        let ours = self.occupied_by(self.turn);
+        let theirs = self.occupied_by(self.turn.opposite());
+        let their_king = self.pieces_of(self.turn.opposite(), Piece::King);
+
+        // Squares from which each piece type would attack the enemy king.
+        let mut check_squares = [BitBoard::EMPTY; 6];
+        if let Some(king_sq) = their_king.next_square().filter(|_| include_checks) {
+            let bishop_rays = get_bishop_moves(king_sq, self.occupied);
+            let rook_rays = get_rook_moves(king_sq, self.occupied);
+
+            check_squares[Piece::Pawn as usize] =
+                BitBoard(PAWN_ATTACKS[self.turn.opposite() as usize][king_sq as usize]);
+            check_squares[Piece::Knight as usize] = BitBoard(KNIGHT_ATTACKS[king_sq as usize]);
+            check_squares[Piece::Bishop as usize] = bishop_rays;
+            check_squares[Piece::Rook as usize] = rook_rays;
+            check_squares[Piece::Queen as usize] = bishop_rays | rook_rays;
+        }
+
+        self.generate_pawn_tactical_moves(moves, &info, check_squares[Piece::Pawn as usize]);
+
+        for piece in [
+            Piece::Knight,
+            Piece::Bishop,
+            Piece::Rook,
+            Piece::Queen,
+            Piece::King,
+        ] {
+            for from in self.pieces_of(self.turn, piece) {
+                let attacks = piece_attacks(piece, self.turn, from, self.occupied) & !ours;
+
+                let targets = attacks & (theirs | (self.empty & check_squares[piece as usize]));
+
+                let targets = match piece {
+                    Piece::King => self.safe_king_squares(from, targets),
+                    _ => targets & info.allowed(from),
+                };
+
+                self.push_moves(moves, from, piece, targets);
+            }
+        }
+    }
 
-            let (our_occupied, their_occupied) = match pawn_color {
-                Color::White => (self.white_occupied, self.black_occupied),
-                Color::Black => (self.black_occupied, self.white_occupied),
+    /// Generates the legal moves `generate_tactical_moves_into` leaves out
+    /// without checks: non-capturing moves that do not promote, castling
+    /// included.
+    pub fn generate_quiet_moves_into(&self, moves: &mut MoveList) {
+        moves.clear();
+
+        let info = self.check_info();
+
+        if info.checkers.count() < 2 {
+            let (forward_dir, start_rank, promotion_rank): (i8, Rank, Rank) = match self.turn {
+                Color::White => (8, Rank::Second, Rank::Seventh),
+                Color::Black => (-8, Rank::Seventh, Rank::Second),
             };
 
-            let target_sq_idx = (from_sq_idx as i8 + forward_dir) as u8;
-            if target_sq_idx < 64 && (self.empty.0 & 1u64 << target_sq_idx) != 0 {
-                if rank == promotion_rank {
-                    for &promo_piece in &[Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight] {
-                        moves.push(Move {
-                            from: Square::from_index(from_sq_idx as u8),
-                            to: Square::from_index(target_sq_idx as u8),
-                            piece: Piece::Pawn,
-                            promotion: Some(promo_piece),
-                            captured_piece: None,
-                            flags: Flags::Promotion,
-                        });
-                    }
-                } else {
-                    moves.push(Move {
-                        from: Square::from_index(from_sq_idx as u8),
-                        to: Square::from_index(target_sq_idx as u8),
-                        piece: Piece::Pawn,
-                        promotion: None,
-                        captured_piece: None,
-                        flags: Flags::Normal,
-                    });
+            for from in self.pieces_of(self.turn, Piece::Pawn) {
+                let push = Square::from_index((from as i8 + forward_dir) as u8);
+                if from.rank() == promotion_rank || !self.empty.has(push) {
+                    continue;
                 }
 
-                if rank == start_rank {
-                    let double_target_sq_idx = (target_sq_idx as i8 + forward_dir) as u8;
-                    if double_target_sq_idx < 64
-                        && (self.empty.0 & 1u64 << double_target_sq_idx) != 0
-                    {
+                let allowed = info.allowed(from);
+                if allowed.has(push) {
+                    self.push_pawn_moves(moves, from, push, false);
+                }
+
+                if from.rank() == start_rank {
+                    let double_push = Square::from_index((push as i8 + forward_dir) as u8);
+                    if self.empty.has(double_push) && allowed.has(double_push) {
                         moves.push(Move {
-                            from: Square::from_index(from_sq_idx as u8),
-                            to: Square::from_index(double_target_sq_idx as u8),
+                            from,
+                            to: double_push,
                             piece: Piece::Pawn,
                             promotion: None,
                             captured_piece: None,
@@ -139,410 +375,483 @@ impl Board {
                 }
             }
 
-            for &capture_dir in &capture_dirs {
-                let target_sq_idx = (from_sq_idx as i8 + capture_dir) as u8;
-                let target_rank = target_sq_idx / 8;
-                let target_file = target_sq_idx % 8;
+            for piece in [Piece::Knight, Piece::Bishop, Piece::Rook, Piece::Queen] {
+                for from in self.pieces_of(self.turn, piece) {
+                    let attacks = piece_attacks(piece, self.turn, from, self.occupied);
 
-                if target_sq_idx < 64
-                    && (target_rank as i8 - rank as i8).abs() == 1
-                    && (target_file as i8 - file as i8).abs() == 1
-                {
-                    let target_bit = BitBoard(1u64 << target_sq_idx);
-                    if their_occupied.0 & target_bit.0 != 0 {
-                        let captured_piece_type = self
-                            .piece_on_square(Square::from_index(target_sq_idx as u8))
-                            .map(|(p, _)| p);
-
-                        if rank == promotion_rank {
-                            for &promo_piece in
-                                &[Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight]
-                            {
-                                moves.push(Move {
-                                    from: Square::from_index(from_sq_idx as u8),
-                                    to: Square::from_index(target_sq_idx as u8),
-                                    piece: Piece::Pawn,
-                                    promotion: Some(promo_piece),
-                                    captured_piece: captured_piece_type,
-                                    flags: Flags::PromotionCapture,
-                                });
-                            }
-                        } else {
-                            moves.push(Move {
-                                from: Square::from_index(from_sq_idx as u8),
-                                to: Square::from_index(target_sq_idx as u8),
-                                piece: Piece::Pawn,
-                                promotion: None,
-                                captured_piece: captured_piece_type,
-                                flags: Flags::Capture,
-                            });
-                        }
-                    }
+                    self.push_moves(
+                        moves,
+                        from,
+                        piece,
+                        attacks & self.empty & info.allowed(from),
+                    );
                 }
             }
+        }
 
-            our_pawns &= our_pawns - BitBoard(1); // Clear the least significant bit
+        if let Some(king) = info.king {
+            let targets = self.safe_king_squares(king, KING_ATTACKS[king as usize] & self.empty);
+            self.push_moves(moves, king, Piece::King, targets);
+            self.generate_castling_moves(moves, &info);
         }
+    }
 
-        if let Some(ep_sq) = self.en_passant_square {
-            let attackers = match self.turn {
-                Color::White => {
-                    PAWN_ATTACKS[self.turn.opposite() as usize][ep_sq as usize] & self.white_pawns.0
-                }
-                Color::Black => {
-                    PAWN_ATTACKS[self.turn.opposite() as usize][ep_sq as usize] & self.black_pawns.0
-                }
-            };
+    /// Whether `mv` is legal here. Meant for moves that come from another
+    /// position, like hash moves and killers, so only the moves of the
+    /// piece type that moves are generated.
+    pub fn is_legal(&self, mv: &Move) -> bool {
+        if self.piece_on_square(mv.from) != Some((mv.piece, self.turn)) {
+            return false;
+        }
 
-            for from_idx in BitBoard(attackers).into_iter() {
-                moves.push(Move {
-                    from: from_idx,
-                    to: ep_sq,
-                    piece: Piece::Pawn,
-                    promotion: None,
-                    captured_piece: Some(Piece::Pawn), // En passant captures a pawn
-                    flags: Flags::EnPassant,
-                });
+        let info = self.check_info();
+        let mut moves = MoveList::new();
+
+        if mv.piece == Piece::King {
+            self.generate_king_moves(&mut moves, &info);
+        } else if info.checkers.count() < 2 {
+            match mv.piece {
+                Piece::Pawn => self.generate_pawn_moves(&mut moves, &info),
+                Piece::Knight => self.generate_knight_moves(&mut moves, &info),
+                Piece::Bishop => self.generate_bishop_moves(&mut moves, &info),
+                Piece::Rook => self.generate_rook_moves(&mut moves, &info),
+                _ => self.generate_queen_moves(&mut moves, &info),
             }
         }
+
+        moves.contains(mv)
     }
 
-    pub fn generate_knight_moves(&self, moves: &mut Vec<Move>) {
-        let (mut knights, our_occupied) = if self.turn == Color::White {
-            (self.white_knights.0, self.white_occupied.0)
-        } else {
-            (self.black_knights.0, self.black_occupied.0)
+    fn generate_pawn_tactical_moves(
+        &self,
+        moves: &mut MoveList,
+        info: &CheckInfo,
+        check_squares: BitBoard,
+    ) {
+        let (forward_dir, start_rank, promotion_rank): (i8, Rank, Rank) = match self.turn {
+            Color::White => (8, Rank::Second, Rank::Seventh),
+            Color::Black => (-8, Rank::Seventh, Rank::Second),
         };
+        let theirs = self.occupied_by(self.turn.opposite());
 
-        while knights != 0 {
-            let from_sq_idx = knights.trailing_zeros() as u8;
+        for from in self.pieces_of(self.turn, Piece::Pawn) {
+            let allowed = info.allowed(from);
+            let promotes = from.rank() == promotion_rank;
+            let captures =
+                BitBoard(PAWN_ATTACKS[self.turn as usize][from as usize]) & theirs & allowed;
 
-            for &offset in &KNIGHT_MOVES {
-                let target_sq_idx_signed = from_sq_idx as i8 + offset;
-
-                if target_sq_idx_signed >= 0 && target_sq_idx_signed < 64 {
-                    let target_sq_idx = target_sq_idx_signed as u8;
-                    let target_bit = 1u64 << target_sq_idx;
-
-                    let from_rank = from_sq_idx / 8;
-                    let from_file = from_sq_idx % 8;
-                    let target_rank = target_sq_idx / 8;
-                    let target_file = target_sq_idx % 8;
-
-                    let rank_diff = (from_rank as i8 - target_rank as i8).abs();
-                    let file_diff = (from_file as i8 - target_file as i8).abs();
-
-                    if rank_diff == 1 && file_diff == 2 || rank_diff == 2 && file_diff == 1 {
-                        if our_occupied & target_bit == 0 {
-                            let captured_piece = self
-                                .piece_on_square(Square::from_index(target_sq_idx as u8))
-                                .map(|(p, _)| p);
+            for to in captures {
+                self.push_pawn_moves(moves, from, to, promotes);
+            }
 
-                            let mut flags = Flags::Normal;
+            let push = Square::from_index((from as i8 + forward_dir) as u8);
+            if !self.empty.has(push) {
+                continue;
+            }
 
-                            if captured_piece.is_some() {
-                                flags = Flags::Capture;
-                            }
+            if promotes || check_squares.has(push) {
+                if allowed.has(push) {
+                    self.push_pawn_moves(moves, from, push, promotes);
+                }
+                if promotes {
+                    continue;
+                }
+            }
 
-                            moves.push(Move {
-                                from: Square::from_index(from_sq_idx as u8),
-                                to: Square::from_index(target_sq_idx as u8),
-                                piece: Piece::Knight,
-                                promotion: None,
-                                captured_piece,
-                                flags,
-                            })
-                        }
-                    }
+            if from.rank() == start_rank {
+                let double_push = Square::from_index((push as i8 + forward_dir) as u8);
+                if self.empty.has(double_push)
+                    && check_squares.has(double_push)
+                    && allowed.has(double_push)
+                {
+                    moves.push(Move {
+                        from,
+                        to: double_push,
+                        piece: Piece::Pawn,
+                        promotion: None,
+                        captured_piece: None,
+                        flags: Flags::DoublePawnPush,
+                    });
                 }
             }
+        }
 
-            knights &= knights - 1;
+        self.generate_en_passant(moves, info);
+    }
+
+    /// Pushes a non-pawn move to each square of `targets`.
+    fn push_moves(&self, moves: &mut MoveList, from: Square, piece: Piece, targets: BitBoard) {
+        for to in targets {
+            let captured_piece = self.piece_on_square(to).map(|(p, _)| p);
+            moves.push(Move {
+                from,
+                to,
+                piece,
+                promotion: None,
+                captured_piece,
+                flags: if captured_piece.is_some() {
+                    Flags::Capture
+                } else {
+                    Flags::Normal
+                },
+            });
         }
     }
 
-    pub fn generate_king_moves(&self, moves: &mut Vec<Move>) {
-        let (mut kings, our_occupied) = if self.turn == Color::White {
-            (self.white_king, self.white_occupied)
-        } else {
-            (self.black_king, self.black_occupied)
-        };
+    /// Pushes a single pawn push or capture, or all four promotions of it.
+    fn push_pawn_moves(&self, moves: &mut MoveList, from: Square, to: Square, promotes: bool) {
+        let captured_piece = self.piece_on_square(to).map(|(p, _)| p);
+
+        if !promotes {
+            moves.push(Move {
+                from,
+                to,
+                piece: Piece::Pawn,
+                promotion: None,
+                captured_piece,
+                flags: if captured_piece.is_some() {
+                    Flags::Capture
+                } else {
+                    Flags::Normal
+                },
+            });
+            return;
+        }
 
-        while let Some(from_sq) = Square::try_index(kings.0.trailing_zeros() as usize) {
-            let attacks = KING_ATTACKS[from_sq as usize] & !our_occupied;
+        for promo_piece in [Piece::Queen, Piece::Rook, Piece::Bishop, Piece::Knight] {
+            moves.push(Move {
+                from,
+                to,
+                piece: Piece::Pawn,
+                promotion: Some(promo_piece),
+                captured_piece,
+                flags: if captured_piece.is_some() {
+                    Flags::PromotionCapture
+                } else {
+                    Flags::Promotion
+                },
+            });
+        }
+    }
 
-            kings &= kings - BitBoard(1); // Clear the least significant bit
+    pub fn generate_legal_moves(&self) -> MoveList {
+        let mut moves = MoveList::new();
+        self.generate_legal_moves_into(&mut moves);
+        moves
+    }
 
-            for to_sq in attacks.into_iter() {
-                if self.is_square_attacked(to_sq, self.turn.opposite()) {
-                    continue;
-                }
+    /// Restores the full move from a packed one, taking the moving and
+    /// captured pieces from the board. `None` if none of our pieces stands
+    /// on its from square. The move is not checked for legality.
+    pub fn unpack_move(&self, packed: PackedMove) -> Option<Move> {
+        let (from, to) = (packed.origin(), packed.destination());
+        let piece = self
+            .piece_on_square(from)
+            .filter(|&(_, color)| color == self.turn)?
+            .0;
+
+        let flag = packed.flag();
+        let captured_piece = match flag {
+            PackedMove::EN_PASSANT => Some(Piece::Pawn),
+            _ if flag & PackedMove::CAPTURE != 0 => self.piece_on_square(to).map(|(p, _)| p),
+            _ => None,
+        };
 
-                let cap = self.piece_on_square(to_sq).map(|(p, _)| p);
-                let flags = if cap.is_some() {
-                    Flags::Capture
-                } else {
-                    Flags::Normal
-                };
+        let flags = match flag {
+            PackedMove::DOUBLE_PAWN_PUSH => Flags::DoublePawnPush,
+            PackedMove::CASTLING => Flags::Castling,
+            PackedMove::CAPTURE => Flags::Capture,
+            PackedMove::EN_PASSANT => Flags::EnPassant,
+            _ if flag & PackedMove::PROMOTION == 0 => Flags::Normal,
+            _ if flag & PackedMove::CAPTURE != 0 => Flags::PromotionCapture,
+            _ => Flags::Promotion,
+        };
 
-                moves.push(Move {
-                    from: from_sq,
-                    to: to_sq,
-                    piece: Piece::King,
-                    promotion: None,
-                    captured_piece: cap,
-                    flags,
-                });
-            }
+        Some(Move {
+            from,
+            to,
+            piece,
+            captured_piece,
+            promotion: packed.promotion(),
+            flags,
+        })
+    }
 
-            let opp = self.turn.opposite();
-            let rights = self.castling_rights;
-
-            match self.turn {
-                Color::White if from_sq == Square::E1 => {
-                    if rights & W_KINGSIDE_RIGHTS != 0 {
-                        if self.empty.has(Square::G1) && self.empty.has(Square::F1) {
-                            let opp = self.turn.opposite();
-                            if !self.is_square_attacked(Square::E1, opp)
-                                && !self.is_square_attacked(Square::F1, opp)
-                                && !self.is_square_attacked(Square::G1, opp)
-                            {
-                                moves.push(Move {
-                                    from: Square::E1,
-                                    to: Square::G1,
-                                    piece: Piece::King,
-                                    promotion: None,
-                                    captured_piece: None,
-                                    flags: Flags::Castling,
-                                });
-                            }
-                        }
-                    }
+    fn generate_pawn_moves(&self, moves: &mut MoveList, info: &CheckInfo) {
+        // `promotion_rank` is the rank a pawn promotes *from*.
+        let (forward_dir, start_rank, promotion_rank): (i8, Rank, Rank) = match self.turn {
+            Color::White => (8, Rank::Second, Rank::Seventh),
+            Color::Black => (-8, Rank::Seventh, Rank::Second),
+        };
+        let theirs = self.occupied_by(self.turn.opposite());
 
-                    if rights & W_QUEENSIDE_RIGHTS != 0 {
-                        if self.empty.has(Square::C1)
-                            && self.empty.has(Square::D1)
-                            && self.empty.has(Square::B1)
-                        {
-                            let opp = self.turn.opposite();
-                            if !self.is_square_attacked(Square::E1, opp)
-                                && !self.is_square_attacked(Square::D1, opp)
-                                && !self.is_square_attacked(Square::C1, opp)
-                            {
-                                moves.push(Move {
-                                    from: Square::E1,
-                                    to: Square::C1,
-                                    piece: Piece::King,
-                                    promotion: None,
-                                    captured_piece: None,
-                                    flags: Flags::Castling,
-                                });
-                            }
-                        }
-                    }
+        for from in self.pieces_of(self.turn, Piece::Pawn) {
+            let allowed = info.allowed(from);
+            let promotes = from.rank() == promotion_rank;
+
+            let push = Square::from_index((from as i8 + forward_dir) as u8);
+            if self.empty.has(push) {
+                if allowed.has(push) {
+                    self.push_pawn_moves(moves, from, push, promotes);
                 }
-                Color::Black if from_sq == Square::E8 => {
-                    if rights & B_KINGSIDE_RIGHTS != 0 {
-                        if self.empty.has(Square::G8) && self.empty.has(Square::F8) {
-                            let opp = self.turn.opposite();
-                            if !self.is_square_attacked(Square::E8, opp)
-                                && !self.is_square_attacked(Square::F8, opp)
-                                && !self.is_square_attacked(Square::G8, opp)
-                            {
-                                moves.push(Move {
-                                    from: Square::E8,
-                                    to: Square::G8,
-                                    piece: Piece::King,
-                                    promotion: None,
-                                    captured_piece: None,
-                                    flags: Flags::Castling,
-                                });
-                            }
-                        }
-                    }
 
-                    if rights & B_QUEENSIDE_RIGHTS != 0 {
-                        if self.empty.has(Square::C8)
-                            && self.empty.has(Square::D8)
-                            && self.empty.has(Square::B8)
-                        {
-                            let opp = self.turn.opposite();
-                            if !self.is_square_attacked(Square::E8, opp)
-                                && !self.is_square_attacked(Square::D8, opp)
-                                && !self.is_square_attacked(Square::C8, opp)
-                            {
-                                moves.push(Move {
-                                    from: Square::E8,
-                                    to: Square::C8,
-                                    piece: Piece::King,
-                                    promotion: None,
-                                    captured_piece: None,
-                                    flags: Flags::Castling,
-                                });
-                            }
-                        }
+                if from.rank() == start_rank {
+                    let double_push = Square::from_index((push as i8 + forward_dir) as u8);
+                    if self.empty.has(double_push) && allowed.has(double_push) {
+                        moves.push(Move {
+                            from,
+                            to: double_push,
+                            piece: Piece::Pawn,
+                            promotion: None,
+                            captured_piece: None,
+                            flags: Flags::DoublePawnPush,
+                        });
                     }
                 }
-                _ => {}
+            }
+
+            let captures =
+                BitBoard(PAWN_ATTACKS[self.turn as usize][from as usize]) & theirs & allowed;
+            for to in captures {
+                self.push_pawn_moves(moves, from, to, promotes);
             }
         }
+
+        self.generate_en_passant(moves, info);
     }
 
-    pub fn generate_rook_moves(&self, moves: &mut Vec<Move>) {
-        let mut rooks = if self.turn == Color::White {
-            self.white_rooks
-        } else {
-            self.black_rooks
+    fn generate_en_passant(&self, moves: &mut MoveList, info: &CheckInfo) {
+        let Some(ep_sq) = self.en_passant_square else {
+            return;
         };
 
-        let blockers = self.occupied;
-
-        while rooks != BitBoard::EMPTY {
-            let rook_sq = Square::from_index(rooks.0.trailing_zeros() as u8);
-            let mut attacks = get_rook_moves(rook_sq, blockers);
-
-            while attacks != BitBoard::EMPTY {
-                let target_sq = Square::from_index(attacks.0.trailing_zeros() as u8);
-                let piece_data = self.piece_on_square(target_sq);
-                let mut flags = Flags::Normal;
-
-                attacks &= attacks - BitBoard(1);
-
-                if piece_data.is_some() {
-                    if piece_data.unwrap().1 == self.turn {
-                        continue;
-                    }
-                    flags = Flags::Capture;
-                }
+        let attackers = BitBoard(PAWN_ATTACKS[self.turn.opposite() as usize][ep_sq as usize])
+            & self.pieces_of(self.turn, Piece::Pawn);
 
+        for from in attackers {
+            if self.en_passant_is_legal(from, ep_sq, info) {
                 moves.push(Move {
-                    from: rook_sq,
-                    to: target_sq,
-                    piece: Piece::Rook,
+                    from,
+                    to: ep_sq,
+                    piece: Piece::Pawn,
                     promotion: None,
-                    captured_piece: piece_data.map(|(p, _)| p),
-                    flags,
+                    captured_piece: Some(Piece::Pawn), // En passant captures a pawn
+                    flags: Flags::EnPassant,
                 });
             }
-
-            rooks &= rooks - BitBoard(1); // Clear the least significant bit
         }
     }
 
-    pub fn generate_bishop_moves(&self, moves: &mut Vec<Move>) {
-        let mut bishops = if self.turn == Color::White {
-            self.white_bishops
-        } else {
-            self.black_bishops
+    /// En passant takes two pieces off the board at once, so pins and checks
+    /// are checked on the resulting occupancy. That also covers the case of
+    /// both pawns shielding the king from a rook or queen along their rank.
+    fn en_passant_is_legal(&self, from: Square, to: Square, info: &CheckInfo) -> bool {
+        let Some(king) = info.king else {
+            return true;
         };
 
-        let blockers = self.occupied;
+        let them = self.turn.opposite();
+        let captured = Square::new(to.file(), from.rank()).unwrap();
 
-        while bishops != BitBoard::EMPTY {
-            let bishop_sq = Square::from_index(bishops.0.trailing_zeros() as u8);
-            let mut attacks = get_bishop_moves(bishop_sq, blockers);
+        // A pawn or knight check is only answered by taking the checker.
+        let leapers = self.pieces_of(them, Piece::Pawn) | self.pieces_of(them, Piece::Knight);
+        if !(info.checkers & leapers & !captured.bb()).is_empty() {
+            return false;
+        }
 
-            while attacks != BitBoard::EMPTY {
-                let target_sq = Square::from_index(attacks.0.trailing_zeros() as u8);
-                let piece_data = self.piece_on_square(target_sq);
-                attacks &= attacks - BitBoard(1); // Clear the least significant bit
+        let occupied = (self.occupied ^ from.bb() ^ captured.bb()) | to.bb();
+        let diagonal = self.pieces_of(them, Piece::Bishop) | self.pieces_of(them, Piece::Queen);
+        let straight = self.pieces_of(them, Piece::Rook) | self.pieces_of(them, Piece::Queen);
 
-                let mut flags = Flags::Normal;
+        (get_bishop_moves(king, occupied) & diagonal).is_empty()
+            && (get_rook_moves(king, occupied) & straight).is_empty()
+    }
 
-                if piece_data.is_some() {
-                    if piece_data.unwrap().1 == self.turn {
-                        continue;
-                    }
-                    flags = Flags::Capture;
-                }
+    pub fn generate_knight_moves(&self, moves: &mut MoveList, info: &CheckInfo) {
+        let ours = self.occupied_by(self.turn);
 
-                moves.push(Move {
-                    from: bishop_sq,
-                    to: target_sq,
-                    piece: Piece::Bishop,
-                    promotion: None,
-                    captured_piece: piece_data.map(|(p, _)| p),
-                    flags,
-                });
-            }
+        // A pinned knight can never stay on the pin line.
+        for from in self.pieces_of(self.turn, Piece::Knight) & !info.pinned {
+            let targets = BitBoard(KNIGHT_ATTACKS[from as usize]) & !ours & info.allowed(from);
+            self.push_moves(moves, from, Piece::Knight, targets);
+        }
+    }
 
-            bishops &= bishops - BitBoard(1); // Clear the least significant bit
+    /// The squares of `targets` the king on `from` can step to without
+    /// being in check.
+    fn safe_king_squares(&self, from: Square, targets: BitBoard) -> BitBoard {
+        // Without the king on the board, a slider checking it along a line
+        // also covers the square behind it.
+        let blockers = self.occupied ^ from.bb();
+        let mut safe = BitBoard::EMPTY;
+
+        for to in targets {
+            if !self.is_square_attacked_through(to, self.turn.opposite(), blockers) {
+                safe |= to.bb();
+            }
         }
+
+        safe
     }
 
-    pub fn generate_queen_moves(&self, moves: &mut Vec<Move>) {
-        let mut queens = if self.turn == Color::White {
-            self.white_queens
-        } else {
-            self.black_queens
+    pub fn generate_king_moves(&self, moves: &mut MoveList, info: &CheckInfo) {
+        let Some(from_sq) = info.king else {
+            return;
         };
-        let blockers = self.occupied;
 
-        while queens != BitBoard::EMPTY {
-            let queen_sq = Square::from_index(queens.0.trailing_zeros() as u8);
-            let mut attacks = get_queen_moves(queen_sq, blockers);
+        let attacks = KING_ATTACKS[from_sq as usize] & !self.occupied_by(self.turn);
+        self.push_moves(
+            moves,
+            from_sq,
+            Piece::King,
+            self.safe_king_squares(from_sq, attacks),
+        );
+        self.generate_castling_moves(moves, info);
+    }
 
-            while attacks != BitBoard::EMPTY {
-                let target_sq = Square::from_index(attacks.0.trailing_zeros() as u8);
-                let piece_data = self.piece_on_square(target_sq);
+    fn generate_castling_moves(&self, moves: &mut MoveList, info: &CheckInfo) {
+        let Some(from_sq) = info.king else {
+            return;
+        };
 
-                attacks &= attacks - BitBoard(1);
+        if !info.checkers.is_empty() {
+            return;
+        }
 
-                let mut flags = Flags::Normal;
+        let rights = self.castling_rights;
+        // The rights should already be gone when a rook has left, but a
+        // castle without its rook would conjure one up.
+        let rooks = self.pieces_of(self.turn, Piece::Rook);
+
+        match self.turn {
+            Color::White if from_sq == Square::E1 => {
+                if rights & W_KINGSIDE_RIGHTS != 0 && rooks.has(Square::H1) {
+                    if self.empty.has(Square::G1) && self.empty.has(Square::F1) {
+                        let opp = self.turn.opposite();
+                        if !self.is_square_attacked(Square::E1, opp)
+                            && !self.is_square_attacked(Square::F1, opp)
+                            && !self.is_square_attacked(Square::G1, opp)
+                        {
+                            moves.push(Move {
+                                from: Square::E1,
+                                to: Square::G1,
+                                piece: Piece::King,
+                                promotion: None,
+                                captured_piece: None,
+                                flags: Flags::Castling,
+                            });
+                        }
+                    }
+                }
 
-                if piece_data.is_some() {
-                    if piece_data.unwrap().1 == self.turn {
-                        continue;
+                if rights & W_QUEENSIDE_RIGHTS != 0 && rooks.has(Square::A1) {
+                    if self.empty.has(Square::C1)
+                        && self.empty.has(Square::D1)
+                        && self.empty.has(Square::B1)
+                    {
+                        let opp = self.turn.opposite();
+                        if !self.is_square_attacked(Square::E1, opp)
+                            && !self.is_square_attacked(Square::D1, opp)
+                            && !self.is_square_attacked(Square::C1, opp)
+                        {
+                            moves.push(Move {
+                                from: Square::E1,
+                                to: Square::C1,
+                                piece: Piece::King,
+                                promotion: None,
+                                captured_piece: None,
+                                flags: Flags::Castling,
+                            });
+                        }
+                    }
+                }
+            }
+            Color::Black if from_sq == Square::E8 => {
+                if rights & B_KINGSIDE_RIGHTS != 0 && rooks.has(Square::H8) {
+                    if self.empty.has(Square::G8) && self.empty.has(Square::F8) {
+                        let opp = self.turn.opposite();
+                        if !self.is_square_attacked(Square::E8, opp)
+                            && !self.is_square_attacked(Square::F8, opp)
+                            && !self.is_square_attacked(Square::G8, opp)
+                        {
+                            moves.push(Move {
+                                from: Square::E8,
+                                to: Square::G8,
+                                piece: Piece::King,
+                                promotion: None,
+                                captured_piece: None,
+                                flags: Flags::Castling,
+                            });
+                        }
                     }
-                    flags = Flags::Capture;
                 }
 
-                moves.push(Move {
-                    from: queen_sq,
-                    to: target_sq,
-                    piece: Piece::Queen,
-                    promotion: None,
-                    captured_piece: piece_data.map(|(p, _)| p),
-                    flags,
-                });
+                if rights & B_QUEENSIDE_RIGHTS != 0 && rooks.has(Square::A8) {
+                    if self.empty.has(Square::C8)
+                        && self.empty.has(Square::D8)
+                        && self.empty.has(Square::B8)
+                    {
+                        let opp = self.turn.opposite();
+                        if !self.is_square_attacked(Square::E8, opp)
+                            && !self.is_square_attacked(Square::D8, opp)
+                            && !self.is_square_attacked(Square::C8, opp)
+                        {
+                            moves.push(Move {
+                                from: Square::E8,
+                                to: Square::C8,
+                                piece: Piece::King,
+                                promotion: None,
+                                captured_piece: None,
+                                flags: Flags::Castling,
+                            });
+                        }
+                    }
+                }
             }
+            _ => {}
+        }
+    }
+
+    pub fn generate_rook_moves(&self, moves: &mut MoveList, info: &CheckInfo) {
+        let ours = self.occupied_by(self.turn);
 
-            queens &= queens - BitBoard(1); // Clear the least significant bit
+        for from in self.pieces_of(self.turn, Piece::Rook) {
+            let targets = get_rook_moves(from, self.occupied) & !ours & info.allowed(from);
+            self.push_moves(moves, from, Piece::Rook, targets);
         }
     }
 
-    pub fn generate_sliding_moves(&self, moves: &mut Vec<Move>, piece: Piece) {
-        let mut piece_bitboard = match piece {
-            Piece::Rook => {
-                if self.turn == Color::White {
-                    self.white_rooks
-                } else {
-                    self.black_rooks
-                }
-            }
-            Piece::Bishop => {
-                if self.turn == Color::White {
-                    self.white_bishops
-                } else {
-                    self.black_bishops
-                }
-            }
-            Piece::Queen => {
-                if self.turn == Color::White {
-                    self.white_queens
-                } else {
-                    self.black_queens
-                }
-            }
-            _ => unreachable!("Should not be called with a non sliding piece"),
-        };
-        let our_occupied = if self.turn == Color::White {
-            self.white_occupied
-        } else {
-            self.black_occupied
-        };
+    pub fn generate_bishop_moves(&self, moves: &mut MoveList, info: &CheckInfo) {
+        let ours = self.occupied_by(self.turn);
+
+        for from in self.pieces_of(self.turn, Piece::Bishop) {
+            let targets = get_bishop_moves(from, self.occupied) & !ours & info.allowed(from);
+            self.push_moves(moves, from, Piece::Bishop, targets);
+        }
+    }
+
+    pub fn generate_queen_moves(&self, moves: &mut MoveList, info: &CheckInfo) {
+        let ours = self.occupied_by(self.turn);
 
-        let mut piece_bb = piece_bitboard.0;
+        for from in self.pieces_of(self.turn, Piece::Queen) {
+            let targets = get_queen_moves(from, self.occupied) & !ours & info.allowed(from);
+            self.push_moves(moves, from, Piece::Queen, targets);
+        }
+    }
+
+    /// Ray-walking slider generator, kept as a reference for the magic
+    /// lookups in tests. Pseudo-legal: pins and checks are ignored.
+    #[cfg(test)]
+    pub fn generate_sliding_moves(&self, moves: &mut MoveList, piece: Piece) {
+        use crate::{consts::DIRECTION_OFFSETS, precomputed::NumSquaresToTheEdge};
+
+        assert!(
+            matches!(piece, Piece::Rook | Piece::Bishop | Piece::Queen),
+            "Should not be called with a non sliding piece"
+        );
+        let our_occupied = self.occupied_by(self.turn);
+
+        let mut piece_bb = self.pieces_of(self.turn, piece).0;
 
         while piece_bb != 0 {
             let from_sq_idx = piece_bb.trailing_zeros() as u8;
@@ -603,7 +912,10 @@ impl Board {
 #[cfg(test)]
 mod tests {
     use crate::{
-        consts::Square::{D5, E4, E5, F6},
+        consts::{
+            File,
+            Square::{D5, E4, E5, F6},
+        },
         moves,
     };
 
@@ -612,9 +924,9 @@ mod tests {
     #[test]
     fn test_generate_pawn_moves() {
         let board = Board::default();
-        let mut moves: Vec<Move> = Vec::new();
+        let mut moves = MoveList::new();
 
-        board.generate_pawn_moves(&mut moves);
+        board.generate_pawn_moves(&mut moves, &board.check_info());
         assert_eq!(moves.len(), 16);
     }
 
@@ -659,8 +971,8 @@ mod tests {
         let board = Board::from_fen("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2")
             .unwrap();
 
-        let mut moves: Vec<Move> = Vec::new();
-        board.generate_pawn_moves(&mut moves);
+        let mut moves = MoveList::new();
+        board.generate_pawn_moves(&mut moves, &board.check_info());
 
         let piece = board.piece_on_square(Square::E4).unwrap();
 
@@ -680,8 +992,8 @@ mod tests {
             Board::from_fen("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3")
                 .unwrap();
 
-        let mut moves: Vec<Move> = Vec::new();
-        board.generate_pawn_moves(&mut moves);
+        let mut moves = MoveList::new();
+        board.generate_pawn_moves(&mut moves, &board.check_info());
 
         let piece = board.piece_on_square(Square::E5).map(|(p, _)| p).unwrap();
 
@@ -711,8 +1023,8 @@ mod tests {
     #[test]
     fn test_initial_knight_moves() {
         let board = Board::default();
-        let mut moves: Vec<moves::Move> = Vec::new();
-        board.generate_knight_moves(&mut moves);
+        let mut moves = MoveList::new();
+        board.generate_knight_moves(&mut moves, &board.check_info());
         assert_eq!(moves.len(), 4);
     }
 
@@ -720,8 +1032,8 @@ mod tests {
     fn test_centralized_knight_moves() {
         let board =
             Board::from_fen("rnbqkbnr/pppppppp/8/8/3N4/8/PPPPPPPP/RNBQKB1R w KQkq - 4 3").unwrap();
-        let mut moves: Vec<moves::Move> = Vec::new();
-        board.generate_knight_moves(&mut moves);
+        let mut moves = MoveList::new();
+        board.generate_knight_moves(&mut moves, &board.check_info());
 
         assert_eq!(moves.len(), 8);
 
@@ -743,9 +1055,9 @@ mod tests {
             Board::from_fen("rnbqkbnr/ppppp2p/5p2/6p1/2B5/4P3/PPPP1PPP/RNBQK1NR w KQkq - 0 3")
                 .unwrap();
 
-        let mut moves: Vec<moves::Move> = Vec::new();
+        let mut moves = MoveList::new();
 
-        board.generate_sliding_moves(&mut moves, Piece::Bishop);
+        board.generate_bishop_moves(&mut moves, &board.check_info());
 
         assert_eq!(moves.len(), 10);
     }
@@ -756,9 +1068,9 @@ mod tests {
             Board::from_fen("rnbqkbnr/ppp1p2p/3p1p2/6p1/2B3Q1/4P3/PPPP1PPP/RNB1K1NR w KQkq - 0 4")
                 .unwrap();
 
-        let mut moves: Vec<moves::Move> = Vec::new();
+        let mut moves = MoveList::new();
 
-        board.generate_sliding_moves(&mut moves, Piece::Queen);
+        board.generate_queen_moves(&mut moves, &board.check_info());
 
         assert_eq!(moves.len(), 15);
 
@@ -777,15 +1089,47 @@ mod tests {
         assert_eq!(moves1.len(), 14);
     }
 
+    #[test]
+    fn test_magic_sliders_match_ray_reference() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1",
+            "rnbqkbnr/ppp1p2p/3p1p2/6p1/2B3Q1/4P3/PPPP1PPP/RNB1K1NR w KQkq - 0 4",
+            "1k6/8/3q4/8/1B3R2/8/6Q1/K7 w - - 0 1",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            let info = board.check_info();
+            assert!(info.checkers.is_empty() && info.pinned.is_empty());
+
+            for piece in [Piece::Bishop, Piece::Rook, Piece::Queen] {
+                let mut reference = MoveList::new();
+                board.generate_sliding_moves(&mut reference, piece);
+
+                let mut magic = MoveList::new();
+                match piece {
+                    Piece::Bishop => board.generate_bishop_moves(&mut magic, &info),
+                    Piece::Rook => board.generate_rook_moves(&mut magic, &info),
+                    _ => board.generate_queen_moves(&mut magic, &info),
+                }
+
+                let key = |mv: &Move| (mv.from as u8, mv.to as u8);
+                reference.sort_by_key(key);
+                magic.sort_by_key(key);
+                assert_eq!(&magic[..], &reference[..], "{:?} in {}", piece, fen);
+            }
+        }
+    }
+
     #[test]
     fn test_rook_moves() {
         let board =
             Board::from_fen("rnbqkbn1/ppp4r/3p1p2/4p1pp/2B5/3PP2Q/PPP1NPPP/RNB2RK1 b q - 0 7")
                 .unwrap();
 
-        let mut moves: Vec<moves::Move> = Vec::new();
+        let mut moves = MoveList::new();
 
-        board.generate_rook_moves(&mut moves);
+        board.generate_rook_moves(&mut moves, &board.check_info());
 
         assert_eq!(moves.len(), 6);
     }
@@ -796,8 +1140,8 @@ mod tests {
             Board::from_fen("rnbq1bn1/ppp1k2r/3p1p2/4p1pp/2B5/3PPQ2/PPP1NPPP/RNB2RK1 b - - 2 8")
                 .unwrap();
 
-        let mut moves: Vec<moves::Move> = Vec::new();
-        board.generate_king_moves(&mut moves);
+        let mut moves = MoveList::new();
+        board.generate_king_moves(&mut moves, &board.check_info());
         assert_eq!(moves.len(), 2);
     }
 
@@ -807,12 +1151,110 @@ mod tests {
             Board::from_fen("rnbq1bnr/pppp1ppp/2k5/4p3/2K1P3/8/PPPP1PPP/RNBQ1BNR w - - 6 5")
                 .unwrap();
 
-        let mut moves: Vec<moves::Move> = Vec::new();
-        board.generate_king_moves(&mut moves);
+        let mut moves = MoveList::new();
+        board.generate_king_moves(&mut moves, &board.check_info());
 
         assert_eq!(moves.len(), 3);
     }
 
+    #[test]
+    fn test_tactical_moves() {
+        let board =
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+
+        let mut tactical = MoveList::new();
+        board.generate_tactical_moves_into(&mut tactical, false);
+
+        let captures: Vec<Move> = board
+            .generate_legal_moves()
+            .into_iter()
+            .filter(|mv| mv.captured_piece.is_some())
+            .collect();
+
+        assert_eq!(tactical.len(), 8);
+        assert_eq!(tactical.len(), captures.len());
+        assert!(tactical.iter().all(|mv| captures.contains(mv)));
+    }
+
+    #[test]
+    fn test_tactical_moves_promotions_and_checks() {
+        let board = Board::from_fen("1n2k3/P7/8/8/8/8/8/4K2R w - - 0 1").unwrap();
+
+        let mut moves = MoveList::new();
+        board.generate_tactical_moves_into(&mut moves, false);
+
+        // a8=Q/R/B/N and axb8=Q/R/B/N.
+        assert_eq!(moves.len(), 8);
+        assert!(moves.iter().all(|mv| mv.promotion.is_some()));
+
+        board.generate_tactical_moves_into(&mut moves, true);
+
+        let checks: Vec<&Move> = moves.iter().filter(|mv| mv.promotion.is_none()).collect();
+        assert_eq!(checks.len(), 1);
+        assert_eq!((checks[0].from, checks[0].to), (Square::H1, Square::H8));
+    }
+
+    #[test]
+    fn test_tactical_and_quiet_moves_split_legal_moves() {
+        let fens = [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1",
+            "4k3/8/8/8/1b6/8/8/RNBQK1NR w - - 0 1",
+        ];
+
+        for fen in fens {
+            let board = Board::from_fen(fen).unwrap();
+            let legal = board.generate_legal_moves();
+
+            let mut tactical = MoveList::new();
+            let mut quiet = MoveList::new();
+            board.generate_tactical_moves_into(&mut tactical, false);
+            board.generate_quiet_moves_into(&mut quiet);
+
+            let mut split: Vec<String> = tactical
+                .iter()
+                .chain(quiet.iter())
+                .map(|mv| mv.to_uci())
+                .collect();
+            let mut all: Vec<String> = legal.iter().map(|mv| mv.to_uci()).collect();
+            split.sort();
+            all.sort();
+            assert_eq!(split, all, "{}", fen);
+            assert!(quiet.iter().all(Move::is_quiet));
+            assert!(!tactical.iter().any(Move::is_quiet));
+
+            // Moves of every position, checked against this one.
+            for other in fens {
+                for mv in Board::from_fen(other).unwrap().generate_legal_moves() {
+                    assert_eq!(
+                        board.is_legal(&mv),
+                        legal.contains(&mv),
+                        "{:?} in {}",
+                        mv,
+                        fen
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_no_castling_without_rook() {
+        let mut board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        // Rights are left in place on purpose, as a stale or hand-built board
+        // might have them.
+        board.delete_piece(Square::H1);
+        board.delete_piece(Square::A1);
+
+        assert!(board
+            .generate_legal_moves()
+            .iter()
+            .all(|mv| mv.flags != Flags::Castling));
+    }
+
     #[test]
     fn test_castling_moves() {
         let mut board1 = Board::from_fen(
@@ -832,7 +1274,7 @@ mod tests {
             board1.make_move(&mv);
         }
 
-        let mut board2 =
+        let board2 =
             Board::from_fen("r1bqk2r/ppp2ppp/2nb1n2/3pp3/3PP3/2NQB3/PPP2PPP/R3KBNR w KQkq - 6 6")
                 .unwrap();
         let moves2: Vec<moves::Move> = board2
@@ -845,4 +1287,151 @@ mod tests {
 
         assert!(castling_move.is_some(), "Castling move should be available");
     }
+
+    #[test]
+    fn test_double_check_allows_only_king_moves() {
+        // The rook on e8 and the knight on d3 both give check.
+        let board = Board::from_fen("4r2k/8/8/8/8/3n4/8/R3K3 w Q - 0 1").unwrap();
+
+        assert_eq!(
+            board.check_info().checkers,
+            Square::E8.bb() | Square::D3.bb()
+        );
+
+        let moves = board.generate_legal_moves();
+        assert!(!moves.is_empty());
+        assert!(moves.iter().all(|mv| mv.piece == Piece::King));
+        assert!(moves.iter().all(|mv| mv.flags != Flags::Castling));
+    }
+
+    #[test]
+    fn test_pinned_piece_moves_along_the_pin() {
+        let board = Board::from_fen("4r2k/8/8/8/8/8/4R3/4K3 w - - 0 1").unwrap();
+
+        assert_eq!(board.check_info().pinned, Square::E2.bb());
+
+        let rook_moves: Vec<Move> = board
+            .generate_legal_moves()
+            .into_iter()
+            .filter(|mv| mv.piece == Piece::Rook)
+            .collect();
+
+        // e3-e7 and the capture on e8.
+        assert_eq!(rook_moves.len(), 6);
+        assert!(rook_moves.iter().all(|mv| mv.to.file() == File::E));
+    }
+
+    #[test]
+    fn test_check_evasions_block_or_capture() {
+        // The bishop on b4 checks along b4-e1: block on c3 or d2, or step
+        // off the diagonal. Castling out of check is not allowed.
+        let board = Board::from_fen("4k3/8/8/8/1b6/8/8/RNBQK1NR w KQ - 0 1").unwrap();
+
+        let moves = board.generate_legal_moves();
+        let mut evasions: Vec<(Square, Square)> = moves.iter().map(|mv| (mv.from, mv.to)).collect();
+        evasions.sort();
+
+        let mut expected = vec![
+            (Square::B1, Square::C3),
+            (Square::B1, Square::D2),
+            (Square::C1, Square::D2),
+            (Square::D1, Square::D2),
+            (Square::E1, Square::E2),
+            (Square::E1, Square::F2),
+            (Square::E1, Square::F1),
+        ];
+        expected.sort();
+        assert_eq!(evasions, expected);
+    }
+
+    #[test]
+    fn test_en_passant_exposing_king_on_rank() {
+        // Taking on c6 would clear the fifth rank between the rook and king.
+        let board = Board::from_fen("8/8/8/KPp4r/8/8/8/7k w - c6 0 1").unwrap();
+
+        let moves = board.generate_legal_moves();
+        assert!(moves.iter().all(|mv| mv.flags != Flags::EnPassant));
+
+        // Without the rook it is fine.
+        let board = Board::from_fen("8/8/8/KPp5/8/8/8/7k w - c6 0 1").unwrap();
+        assert!(board
+            .generate_legal_moves()
+            .iter()
+            .any(|mv| mv.flags == Flags::EnPassant));
+    }
+
+    #[test]
+    fn test_generated_moves_never_leave_king_in_check() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "8/8/8/2k5/3Pp3/8/8/4K1B1 b - d3 0 1",
+        ] {
+            let mut board = Board::from_fen(fen).unwrap();
+            let them = board.turn.opposite();
+
+            for mv in board.generate_legal_moves() {
+                board.make_move(&mv);
+                assert!(
+                    !board.is_king_in_check(them),
+                    "{:?} is illegal in {}",
+                    mv,
+                    fen
+                );
+                board.unmake_move(&mv);
+            }
+        }
+    }
+
+    #[test]
+    fn test_packed_moves_round_trip() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "1n2k3/P7/8/8/8/8/8/4K2R w K - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+
+            for mv in board.generate_legal_moves() {
+                let packed = PackedMove::from(mv);
+
+                assert!(!packed.is_null());
+                assert_eq!((packed.origin(), packed.destination()), (mv.from, mv.to));
+                assert_eq!(packed.promotion(), mv.promotion);
+                assert_eq!(board.unpack_move(packed), Some(mv), "{}", fen);
+            }
+        }
+
+        // An empty from square, then one of the opponent's pieces.
+        let board = Board::default();
+        assert_eq!(
+            board.unpack_move(PackedMove(E4 as u16 | (E5 as u16) << 6)),
+            None
+        );
+        assert_eq!(
+            board.unpack_move(PackedMove(Square::E7 as u16 | (Square::E6 as u16) << 6)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_move_list() {
+        let board = Board::default();
+        let mut moves = board.generate_legal_moves();
+
+        assert_eq!(moves.len(), 20);
+        assert_eq!(moves.iter().count(), 20);
+        assert_eq!(moves.clone().into_iter().count(), 20);
+
+        moves.sort_by_key(|mv| std::cmp::Reverse(mv.to as u8));
+        assert!(moves
+            .windows(2)
+            .all(|pair| pair[0].to as u8 >= pair[1].to as u8));
+
+        moves.clear();
+        assert!(moves.is_empty());
+        assert_eq!(moves.into_iter().next(), None);
+    }
 }
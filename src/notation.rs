@@ -0,0 +1,394 @@
+use std::fmt;
+
+use crate::{
+    board::{Board, Piece},
+    consts::{File, Rank, Square},
+    moves::{Flags, Move},
+};
+
+/// Why a move in UCI or SAN notation could not be resolved on a board.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoveParseError {
+    /// The text is not a move in the expected notation.
+    Syntax(String),
+    /// Well formed, but no legal move matches.
+    Illegal(String),
+    /// A SAN move that more than one legal move matches.
+    Ambiguous(String),
+}
+
+impl fmt::Display for MoveParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveParseError::Syntax(text) => write!(f, "not a move: {}", text),
+            MoveParseError::Illegal(text) => write!(f, "illegal move: {}", text),
+            MoveParseError::Ambiguous(text) => write!(f, "ambiguous move: {}", text),
+        }
+    }
+}
+
+impl std::error::Error for MoveParseError {}
+
+impl Move {
+    /// The move in UCI long algebraic notation, e.g. `e2e4` or `e7e8q`.
+    /// Castling is written as the king's move.
+    pub fn to_uci(self) -> String {
+        match self.promotion {
+            Some(piece) => format!("{}{}{}", self.from, self.to, piece.to_char()),
+            None => format!("{}{}", self.from, self.to),
+        }
+    }
+
+    fn is_capture(self) -> bool {
+        self.captured_piece.is_some() || self.flags == Flags::EnPassant
+    }
+}
+
+impl Board {
+    /// Finds the legal move written as `text` in UCI notation.
+    pub fn parse_uci_move(&self, text: &str) -> Result<Move, MoveParseError> {
+        let well_formed = matches!(text.len(), 4 | 5)
+            && text.is_char_boundary(2)
+            && Square::from_name(&text[..2]).is_some()
+            && text.get(2..4).and_then(Square::from_name).is_some();
+        if !well_formed {
+            return Err(MoveParseError::Syntax(text.to_string()));
+        }
+
+        self.generate_legal_moves()
+            .into_iter()
+            .find(|mv| mv.to_uci() == text)
+            .ok_or_else(|| MoveParseError::Illegal(text.to_string()))
+    }
+
+    /// The legal move `mv` in standard algebraic notation, e.g. `Nbd7`,
+    /// `exd6`, `e8=Q+` or `O-O-O#`.
+    pub fn to_san(&self, mv: &Move) -> String {
+        let mut san = if mv.flags == Flags::Castling {
+            match mv.to.file() {
+                File::G => "O-O".to_string(),
+                _ => "O-O-O".to_string(),
+            }
+        } else {
+            let mut san = String::new();
+
+            if mv.piece == Piece::Pawn {
+                if mv.is_capture() {
+                    san.push(file_char(mv.from.file()));
+                }
+            } else {
+                san.push(mv.piece.to_char().to_ascii_uppercase());
+
+                // Other moves of the same piece type to the same square.
+                let rivals: Vec<Move> = self
+                    .generate_legal_moves()
+                    .into_iter()
+                    .filter(|other| {
+                        other.piece == mv.piece && other.to == mv.to && other.from != mv.from
+                    })
+                    .collect();
+
+                if !rivals.is_empty() {
+                    if rivals
+                        .iter()
+                        .all(|other| other.from.file() != mv.from.file())
+                    {
+                        san.push(file_char(mv.from.file()));
+                    } else if rivals
+                        .iter()
+                        .all(|other| other.from.rank() != mv.from.rank())
+                    {
+                        san.push(rank_char(mv.from.rank()));
+                    } else {
+                        san.push_str(&mv.from.to_string());
+                    }
+                }
+            }
+
+            if mv.is_capture() {
+                san.push('x');
+            }
+            san.push_str(&mv.to.to_string());
+
+            if let Some(piece) = mv.promotion {
+                san.push('=');
+                san.push(piece.to_char().to_ascii_uppercase());
+            }
+            san
+        };
+
+        let mut after = self.clone();
+        after.make_move(mv);
+        if after.is_king_in_check(self.turn) {
+            san.push(if after.generate_legal_moves().is_empty() {
+                '#'
+            } else {
+                '+'
+            });
+        }
+
+        san
+    }
+
+    /// Finds the legal move written as `text` in SAN. Also accepts `0-0`,
+    /// missing or extra check marks, annotations like `!?`, missing `x` and
+    /// `=`, lowercase promotion pieces and redundant disambiguation.
+    pub fn parse_san(&self, text: &str) -> Result<Move, MoveParseError> {
+        let syntax = || MoveParseError::Syntax(text.to_string());
+
+        let trimmed = text.trim_end_matches(['+', '#', '!', '?']);
+        let trimmed = trimmed.strip_suffix("e.p.").unwrap_or(trimmed).trim_end();
+
+        let moves = self.generate_legal_moves();
+
+        let castle_to = match trimmed {
+            "O-O" | "0-0" => Some(File::G),
+            "O-O-O" | "0-0-0" => Some(File::C),
+            _ => None,
+        };
+        let candidates: Vec<Move> = if let Some(file) = castle_to {
+            moves
+                .into_iter()
+                .filter(|mv| mv.flags == Flags::Castling && mv.to.file() == file)
+                .collect()
+        } else {
+            let mut chars: Vec<char> = trimmed.chars().collect();
+
+            // A trailing letter after the destination rank or `=` is the
+            // promotion piece, so `bxc8b` promotes but `Nb` has no rank.
+            let promotion = match *chars.as_slice() {
+                [.., '=' | '1'..='8', c] if !c.is_ascii_digit() => {
+                    let piece = Piece::from_char(c.to_ascii_lowercase())
+                        .filter(|piece| !matches!(piece, Piece::Pawn | Piece::King))
+                        .ok_or_else(syntax)?;
+                    chars.pop();
+                    if chars.last() == Some(&'=') {
+                        chars.pop();
+                    }
+                    Some(piece)
+                }
+                _ => None,
+            };
+
+            if chars.len() < 2 {
+                return Err(syntax());
+            }
+            let to_name: String = chars.split_off(chars.len() - 2).into_iter().collect();
+            let to = Square::from_name(&to_name).ok_or_else(syntax)?;
+
+            let mut prefix = chars.as_slice();
+            let piece = match prefix.first() {
+                Some(&c) if c.is_ascii_uppercase() => {
+                    prefix = &prefix[1..];
+                    Piece::from_char(c.to_ascii_lowercase()).ok_or_else(syntax)?
+                }
+                _ => Piece::Pawn,
+            };
+
+            let mut from_file = None;
+            let mut from_rank = None;
+            for &c in prefix {
+                match c {
+                    'a'..='h' if from_file.is_none() && from_rank.is_none() => {
+                        from_file = Some(File::index(c as usize - 'a' as usize));
+                    }
+                    '1'..='8' if from_rank.is_none() => {
+                        from_rank = Some(Rank::index(c as usize - '1' as usize));
+                    }
+                    'x' | ':' | '-' => {}
+                    _ => return Err(syntax()),
+                }
+            }
+
+            moves
+                .into_iter()
+                .filter(|mv| {
+                    mv.piece == piece
+                        && mv.to == to
+                        && mv.promotion == promotion
+                        && from_file.is_none_or(|file| mv.from.file() == file)
+                        && from_rank.is_none_or(|rank| mv.from.rank() == rank)
+                })
+                .collect()
+        };
+
+        match candidates.as_slice() {
+            [mv] => Ok(*mv),
+            [] => Err(MoveParseError::Illegal(text.to_string())),
+            _ => Err(MoveParseError::Ambiguous(text.to_string())),
+        }
+    }
+}
+
+fn file_char(file: File) -> char {
+    (b'a' + file as u8) as char
+}
+
+fn rank_char(rank: Rank) -> char {
+    (b'1' + rank as u8) as char
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn san(fen: &str, uci: &str) -> String {
+        let board = Board::from_fen(fen).unwrap();
+        board.to_san(&board.parse_uci_move(uci).unwrap())
+    }
+
+    #[test]
+    fn test_uci_round_trip() {
+        let board = Board::from_fen("8/P7/8/8/8/8/8/k6K w - - 0 1").unwrap();
+        let mv = board.parse_uci_move("a7a8n").unwrap();
+
+        assert_eq!(mv.promotion, Some(Piece::Knight));
+        assert_eq!(mv.to_uci(), "a7a8n");
+
+        for mv in Board::default().generate_legal_moves() {
+            assert_eq!(Board::default().parse_uci_move(&mv.to_uci()), Ok(mv));
+        }
+    }
+
+    #[test]
+    fn test_parse_uci_errors() {
+        let board = Board::default();
+
+        assert_eq!(
+            board.parse_uci_move("e2e5"),
+            Err(MoveParseError::Illegal("e2e5".to_string()))
+        );
+        assert_eq!(
+            board.parse_uci_move("e2"),
+            Err(MoveParseError::Syntax("e2".to_string()))
+        );
+        assert_eq!(
+            board.parse_uci_move("e2x4"),
+            Err(MoveParseError::Syntax("e2x4".to_string()))
+        );
+        assert!(board.parse_uci_move("e2é4").is_err());
+    }
+
+    #[test]
+    fn test_to_san() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(san(start, "e2e4"), "e4");
+        assert_eq!(san(start, "g1f3"), "Nf3");
+
+        // Castling, and a capture by a pawn and a piece.
+        let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        assert_eq!(san(kiwipete, "e1g1"), "O-O");
+        assert_eq!(san(kiwipete, "e1c1"), "O-O-O");
+        assert_eq!(san(kiwipete, "d5e6"), "dxe6");
+        assert_eq!(san(kiwipete, "e5f7"), "Nxf7");
+        assert_eq!(san(kiwipete, "f3f6"), "Qxf6");
+
+        // En passant, promotions and checks.
+        assert_eq!(san("8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1", "c4d3"), "cxd3+");
+        assert_eq!(san("4k3/1P6/8/8/8/8/K7/8 w - - 0 1", "b7b8q"), "b8=Q+");
+        assert_eq!(san("4k3/1P6/8/8/8/8/K7/8 w - - 0 1", "b7b8n"), "b8=N");
+        assert_eq!(san("2r3k1/1P6/8/8/8/8/8/K7 w - - 0 1", "b7c8r"), "bxc8=R+");
+
+        // Checkmate.
+        assert_eq!(san("6k1/5ppp/8/8/8/8/8/K3R3 w - - 0 1", "e1e8"), "Re8#");
+        assert_eq!(
+            san(
+                "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq - 0 2",
+                "d8h4"
+            ),
+            "Qh4#"
+        );
+    }
+
+    #[test]
+    fn test_san_disambiguation() {
+        // Knights on b1 and f1 share a rank, rooks on a1 and a5 a file.
+        let fen = "4k3/8/8/R7/8/8/8/RN2KN2 w - - 0 1";
+        assert_eq!(san(fen, "b1d2"), "Nbd2");
+        assert_eq!(san(fen, "a1a3"), "R1a3");
+        assert_eq!(san(fen, "a5a3"), "R5a3");
+        assert_eq!(san(fen, "a5b5"), "Rb5");
+
+        // Three queens where neither the file nor the rank alone is enough.
+        let fen = "1k6/8/8/8/4Q2Q/8/8/K6Q w - - 0 1";
+        assert_eq!(san(fen, "h4e1"), "Qh4e1");
+        assert_eq!(san(fen, "e4e1"), "Qee1");
+        assert_eq!(san(fen, "h1e1"), "Q1e1");
+
+        // A pinned rival does not count.
+        assert_eq!(san("6k1/8/8/8/8/8/2N1N3/4K3 w - - 0 1", "c2d4"), "Ncd4");
+        assert_eq!(san("4r1k1/8/8/8/8/8/2N1N3/4K3 w - - 0 1", "c2d4"), "Nd4");
+    }
+
+    #[test]
+    fn test_san_round_trip() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "1k6/8/8/8/4Q2Q/8/8/K6Q w - - 0 1",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            for mv in board.generate_legal_moves() {
+                let san = board.to_san(&mv);
+                assert_eq!(board.parse_san(&san), Ok(mv), "{} in {}", san, fen);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_san_variants() {
+        let kiwipete =
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+        let uci = |text: &str| kiwipete.parse_san(text).map(|mv| mv.to_uci());
+
+        assert_eq!(uci("0-0"), Ok("e1g1".to_string()));
+        assert_eq!(uci("O-O-O+"), Ok("e1c1".to_string()));
+        assert_eq!(uci("Qxf6"), Ok("f3f6".to_string()));
+        assert_eq!(uci("Qf6"), Ok("f3f6".to_string()));
+        assert_eq!(uci("Qf3xf6!?"), Ok("f3f6".to_string()));
+        assert_eq!(uci("Nxf7"), Ok("e5f7".to_string()));
+        assert_eq!(uci("de6"), Ok("d5e6".to_string()));
+        assert_eq!(uci("Nb5"), Ok("c3b5".to_string()));
+
+        let promotion = Board::from_fen("2r3k1/1P6/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        for text in ["bxc8=Q+", "bxc8Q", "bc8=q", "b7c8=Q"] {
+            assert_eq!(
+                promotion.parse_san(text).map(|mv| mv.to_uci()),
+                Ok("b7c8q".to_string()),
+                "{}",
+                text
+            );
+        }
+        assert_eq!(
+            promotion.parse_san("b8").map(|mv| mv.to_uci()),
+            Err(MoveParseError::Illegal("b8".to_string()))
+        );
+
+        let en_passant = Board::from_fen("8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1").unwrap();
+        assert_eq!(
+            en_passant.parse_san("cxd3 e.p.").map(|mv| mv.to_uci()),
+            Ok("c4d3".to_string())
+        );
+
+        let knights = Board::from_fen("4k3/8/8/8/8/2N3N1/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            knights.parse_san("Ne4"),
+            Err(MoveParseError::Ambiguous("Ne4".to_string()))
+        );
+        assert_eq!(
+            knights.parse_san("Ke9"),
+            Err(MoveParseError::Syntax("Ke9".to_string()))
+        );
+        assert_eq!(
+            knights.parse_san("Xe4"),
+            Err(MoveParseError::Syntax("Xe4".to_string()))
+        );
+        assert_eq!(
+            knights.parse_san(""),
+            Err(MoveParseError::Syntax(String::new()))
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use crate::{
+    bitboard::BitBoard,
+    board::{Board, Color, Piece},
+    consts::{ADJACENT_FILE_MASKS, FORWARD_FILE_MASKS, PASSED_PAWN_MASKS, PAWN_ATTACKS},
+    evaluation::EvalWeights,
+};
+
+const PAWN_TABLE_SIZE: usize = 1 << 14;
+
+/// Cached pawn structure terms, from White's point of view.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PawnEntry {
+    key: u64,
+    pub mg: i32,
+    pub eg: i32,
+    /// Passed pawns per colour, for the terms that also depend on pieces.
+    pub passed: [BitBoard; 2],
+}
+
+/// Pawn hash table keyed by `Board::pawn_key`. An empty slot has key 0,
+/// which is also the key of a board without pawns, whose entry is all zero.
+#[derive(Debug, Clone)]
+pub struct PawnTable {
+    entries: Vec<PawnEntry>,
+}
+
+impl Default for PawnTable {
+    fn default() -> Self {
+        PawnTable {
+            entries: vec![PawnEntry::default(); PAWN_TABLE_SIZE],
+        }
+    }
+}
+
+impl PawnTable {
+    pub fn clear(&mut self) {
+        self.entries.fill(PawnEntry::default());
+    }
+
+    /// Returns the pawn terms of `board`, evaluating and storing them on a
+    /// miss.
+    pub fn probe(&mut self, board: &Board, weights: &EvalWeights) -> PawnEntry {
+        let slot = &mut self.entries[board.pawn_key as usize & (PAWN_TABLE_SIZE - 1)];
+
+        if slot.key != board.pawn_key {
+            let white = board.pieces_of(Color::White, Piece::Pawn);
+            let black = board.pieces_of(Color::Black, Piece::Pawn);
+            *slot = evaluate_pawns(white, black, weights);
+            slot.key = board.pawn_key;
+        }
+
+        *slot
+    }
+}
+
+/// Doubled, isolated, backward, connected and passed pawn terms.
+pub fn evaluate_pawns(
+    white_pawns: BitBoard,
+    black_pawns: BitBoard,
+    weights: &EvalWeights,
+) -> PawnEntry {
+    let mut entry = PawnEntry::default();
+
+    for (color, ours, theirs) in [
+        (Color::White, white_pawns, black_pawns),
+        (Color::Black, black_pawns, white_pawns),
+    ] {
+        let us = color as usize;
+        let sign = if color == Color::White { 1 } else { -1 };
+        let mut mg = 0;
+        let mut eg = 0;
+        let mut add = |term: [i32; 2]| {
+            mg += term[0];
+            eg += term[1];
+        };
+
+        for sq in ours {
+            let file = sq.file() as usize;
+            let rank = match color {
+                Color::White => sq.rank() as usize,
+                Color::Black => 7 - sq.rank() as usize,
+            };
+            let idx = sq as usize;
+
+            let neighbours = ours.0 & ADJACENT_FILE_MASKS[file];
+            // Only the rearmost pawn of a doubled pair counts as doubled, and
+            // only the frontmost can be passed.
+            let doubled = ours.0 & FORWARD_FILE_MASKS[us][idx] != 0;
+            let isolated = neighbours == 0;
+            let supported = PAWN_ATTACKS[1 - us][idx] & ours.0 != 0;
+            let phalanx = neighbours & (0xffu64 << (sq.rank() as usize * 8)) != 0;
+
+            if doubled {
+                add(weights.doubled_pawn);
+            }
+
+            if isolated {
+                add(weights.isolated_pawn);
+            } else if supported || phalanx {
+                add(weights.connected_pawn);
+            } else {
+                // No friendly pawn level with or behind it can ever defend
+                // it, and it cannot advance safely past an enemy pawn.
+                let stop = match color {
+                    Color::White => idx + 8,
+                    Color::Black => idx - 8,
+                };
+                let behind = ADJACENT_FILE_MASKS[file] & !PASSED_PAWN_MASKS[us][idx];
+                let stop_attacked = PAWN_ATTACKS[us][stop] & theirs.0 != 0;
+
+                if neighbours & behind == 0 && stop_attacked {
+                    add(weights.backward_pawn);
+                }
+            }
+
+            if !doubled && PASSED_PAWN_MASKS[us][idx] & theirs.0 == 0 {
+                add([weights.passed_pawn_mg[rank], weights.passed_pawn_eg[rank]]);
+                entry.passed[us] |= sq.bb();
+            }
+        }
+
+        entry.mg += sign * mg;
+        entry.eg += sign * eg;
+    }
+
+    entry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::Square;
+
+    fn terms(fen: &str) -> PawnEntry {
+        let board = Board::from_fen(fen).unwrap();
+        evaluate_pawns(
+            board.pieces_of(Color::White, Piece::Pawn),
+            board.pieces_of(Color::Black, Piece::Pawn),
+            &EvalWeights::default(),
+        )
+    }
+
+    #[test]
+    fn test_doubled_and_isolated_pawns() {
+        let w = EvalWeights::default();
+        // White: doubled, isolated c-pawns. Black: a passed duo on f7/g7.
+        let entry = terms("4k3/5pp1/8/8/8/2P5/2P5/4K3 w - - 0 1");
+
+        let white = 2 * w.isolated_pawn[0] + w.doubled_pawn[0] + w.passed_pawn_mg[2];
+        let black = 2 * (w.connected_pawn[0] + w.passed_pawn_mg[1]);
+        assert_eq!(entry.mg, white - black);
+        assert_eq!(entry.passed[Color::White as usize], Square::C3.bb());
+        assert_eq!(
+            entry.passed[Color::Black as usize],
+            Square::F7.bb() | Square::G7.bb()
+        );
+    }
+
+    #[test]
+    fn test_passed_pawns_scale_with_rank() {
+        let far = terms("4k3/8/1P6/8/8/8/8/4K3 w - - 0 1");
+        let near = terms("4k3/8/8/8/8/1P6/8/4K3 w - - 0 1");
+        let blocked = terms("4k3/2p5/1P6/8/8/8/8/4K3 w - - 0 1");
+
+        assert!(far.eg > near.eg);
+        assert_eq!(near.passed[Color::White as usize], Square::B3.bb());
+        assert!(blocked.passed[Color::White as usize].is_empty());
+    }
+
+    #[test]
+    fn test_backward_pawn() {
+        let w = EvalWeights::default();
+        // The c5 pawn has left d2 behind, and e4 covers d3. The lone e4
+        // pawn is isolated.
+        let entry = terms("4k3/8/8/2P5/4p3/8/3P4/4K3 w - - 0 1");
+
+        let white = w.passed_pawn_mg[4] + w.backward_pawn[0];
+        let black = w.isolated_pawn[0];
+        assert_eq!(entry.mg, white - black);
+    }
+
+    #[test]
+    fn test_symmetric_structure_is_balanced() {
+        let entry = terms("4k3/pp3ppp/2p5/3p4/3P4/2P5/PP3PPP/4K3 w - - 0 1");
+
+        assert_eq!((entry.mg, entry.eg), (0, 0));
+    }
+
+    #[test]
+    fn test_pawn_table_caches_by_pawn_key() {
+        let board = Board::from_fen("4k3/3pp3/8/8/8/2P5/2P5/4K3 w - - 0 1").unwrap();
+        let weights = EvalWeights::default();
+        let mut table = PawnTable::default();
+
+        let first = table.probe(&board, &weights);
+        assert_eq!(first.mg, terms("4k3/3pp3/8/8/8/2P5/2P5/4K3 w - - 0 1").mg);
+
+        // A stale entry under this key would be returned as is.
+        let slot = board.pawn_key as usize & (PAWN_TABLE_SIZE - 1);
+        table.entries[slot].mg += 1;
+        assert_eq!(table.probe(&board, &weights).mg, first.mg + 1);
+
+        table.clear();
+        assert_eq!(table.probe(&board, &weights), first);
+    }
+}
@@ -1,35 +1,226 @@
-use crate::{bitboard::BitBoard, board::Board, game_result::GameResult, moves::Flags};
-use std::cell::RefCell;
-use crate::moves::Move;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
 
-thread_local! {
-    static MOVE_BUF: RefCell<Vec<Move>> = RefCell::new(Vec::with_capacity(128));
-}
+use crate::{
+    board::Board,
+    moves::{Move, MoveList},
+};
 
-pub fn perft(board: &Board, depth: u32) -> u64 {
+pub fn perft(board: &mut Board, depth: u32) -> u64 {
     if depth == 0 {
         return 1;
     }
 
-    let mut moves = Vec::with_capacity(256);
+    let mut moves = MoveList::new();
     board.generate_legal_moves_into(&mut moves);
-    // 2) Iterate over the clone, recursing
+
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    let mut nodes = 0;
+    for mv in moves {
+        board.make_move(&mv);
+        nodes += perft(board, depth - 1);
+        board.unmake_move(&mv);
+    }
+    nodes
+}
+
+/// Node counts below each root move, in generation order.
+pub fn perft_divide(board: &mut Board, depth: u32) -> Vec<(Move, u64)> {
+    let moves = board.generate_legal_moves();
+
+    moves
+        .into_iter()
+        .map(|mv| {
+            board.make_move(&mv);
+            let nodes = perft(board, depth.saturating_sub(1));
+            board.unmake_move(&mv);
+            (mv, nodes)
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct PerftEntry {
+    key: u64,
+    depth: u32,
+    nodes: u64,
+}
+
+/// Subtree counts keyed by `Board::zobrist_hash` and depth. Transpositions
+/// are counted once per table instead of once per path.
+pub struct PerftTable {
+    entries: Vec<PerftEntry>,
+}
+
+impl PerftTable {
+    /// Allocates the largest power-of-two number of entries that fits in
+    /// `megabytes`.
+    pub fn new(megabytes: usize) -> PerftTable {
+        let count = megabytes.max(1) * 1024 * 1024 / std::mem::size_of::<PerftEntry>();
+
+        PerftTable {
+            entries: vec![PerftEntry::default(); 1 << count.ilog2()],
+        }
+    }
+
+    fn slot(&self, hash: u64) -> usize {
+        hash as usize & (self.entries.len() - 1)
+    }
+}
+
+/// Same count as `perft`, reusing the counts of subtrees already seen.
+pub fn perft_hashed(board: &mut Board, depth: u32, table: &mut PerftTable) -> u64 {
+    // Bulk counting makes the last ply cheaper than a lookup.
+    if depth <= 1 {
+        return perft(board, depth);
+    }
+
+    let slot = table.slot(board.zobrist_hash);
+    let entry = table.entries[slot];
+    if entry.key == board.zobrist_hash && entry.depth == depth {
+        return entry.nodes;
+    }
+
+    let moves = board.generate_legal_moves();
     let mut nodes = 0;
     for mv in moves {
-        let mut board_copy = board.clone();
-        board_copy.make_move(&mv);
-        nodes += perft(&board_copy, depth - 1);
+        board.make_move(&mv);
+        nodes += perft_hashed(board, depth - 1, table);
+        board.unmake_move(&mv);
     }
+
+    table.entries[slot] = PerftEntry {
+        key: board.zobrist_hash,
+        depth,
+        nodes,
+    };
     nodes
 }
 
+/// `perft_divide` with the root moves shared out between `threads` workers.
+/// With `hash_mb` above zero every worker counts through its own
+/// `PerftTable` of that size.
+pub fn perft_parallel(
+    board: &Board,
+    depth: u32,
+    threads: usize,
+    hash_mb: usize,
+) -> Vec<(Move, u64)> {
+    let moves = board.generate_legal_moves();
+    let next = AtomicUsize::new(0);
+    let mut counts = vec![0; moves.len()];
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads.max(1))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut board = board.clone();
+                    let mut table = (hash_mb > 0).then(|| PerftTable::new(hash_mb));
+                    let mut done = Vec::new();
+
+                    // Root moves differ a lot in size, so take them one at a
+                    // time rather than in fixed chunks.
+                    loop {
+                        let idx = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(mv) = moves.get(idx) else {
+                            break;
+                        };
+                        let depth = depth.saturating_sub(1);
+
+                        board.make_move(mv);
+                        let nodes = match table.as_mut() {
+                            Some(table) => perft_hashed(&mut board, depth, table),
+                            None => perft(&mut board, depth),
+                        };
+                        board.unmake_move(mv);
+
+                        done.push((idx, nodes));
+                    }
+
+                    done
+                })
+            })
+            .collect();
+
+        for worker in workers {
+            for (idx, nodes) in worker.join().expect("perft worker panicked") {
+                counts[idx] = nodes;
+            }
+        }
+    });
+
+    moves.into_iter().zip(counts).collect()
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::perft::{perft};
+    use super::*;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
 
     #[test]
     fn test_perft() {
         let mut board = crate::Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
-        assert_eq!(perft(&board, 6), 119060324);
+        assert_eq!(perft(&mut board, 6), 119060324);
+    }
+
+    #[test]
+    fn test_perft_tricky_positions() {
+        for (fen, depth, nodes) in [
+            (
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                3,
+                97862,
+            ),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 5, 674624),
+            (
+                "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                4,
+                422333,
+            ),
+            (
+                "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+                3,
+                62379,
+            ),
+        ] {
+            let mut board = crate::Board::from_fen(fen).unwrap();
+            assert_eq!(perft(&mut board, depth), nodes, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_perft_divide_sums_to_perft() {
+        let mut board = Board::from_fen(KIWIPETE).unwrap();
+        let divide = perft_divide(&mut board, 3);
+
+        assert_eq!(divide.len(), 48);
+        assert_eq!(divide.iter().map(|&(_, nodes)| nodes).sum::<u64>(), 97862);
+    }
+
+    #[test]
+    fn test_hashed_perft_matches_plain() {
+        let mut table = PerftTable::new(1);
+
+        let mut board = Board::from_fen(KIWIPETE).unwrap();
+        assert_eq!(perft_hashed(&mut board, 3, &mut table), 97862);
+
+        // The table is keyed by depth too, so shallower counts of the same
+        // positions do not leak into this one.
+        let mut board = Board::default();
+        assert_eq!(perft_hashed(&mut board, 4, &mut table), 197281);
+        assert_eq!(perft_hashed(&mut board, 5, &mut table), 4865609);
+    }
+
+    #[test]
+    fn test_parallel_perft_matches_divide() {
+        let mut board = Board::from_fen(KIWIPETE).unwrap();
+        let divide = perft_divide(&mut board, 3);
+
+        assert_eq!(perft_parallel(&board, 3, 4, 0), divide);
+        assert_eq!(perft_parallel(&board, 3, 3, 1), divide);
     }
 }
@@ -0,0 +1,716 @@
+use std::fmt::{self, Write};
+
+use crate::{
+    board::{Board, Color},
+    fen::FenError,
+    moves::Move,
+    notation::MoveParseError,
+};
+
+/// Longest movetext line written by `Game::to_pgn`.
+const LINE_WIDTH: usize = 79;
+
+/// The game termination marker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PgnResult {
+    WhiteWins,
+    BlackWins,
+    Draw,
+    /// `*`: unfinished or unknown.
+    Unknown,
+}
+
+impl PgnResult {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PgnResult::WhiteWins => "1-0",
+            PgnResult::BlackWins => "0-1",
+            PgnResult::Draw => "1/2-1/2",
+            PgnResult::Unknown => "*",
+        }
+    }
+
+    fn parse(text: &str) -> Option<PgnResult> {
+        match text {
+            "1-0" => Some(PgnResult::WhiteWins),
+            "0-1" => Some(PgnResult::BlackWins),
+            "1/2-1/2" => Some(PgnResult::Draw),
+            "*" => Some(PgnResult::Unknown),
+            _ => None,
+        }
+    }
+}
+
+/// A move in the game tree with its annotations.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub mv: Move,
+    /// Numeric annotation glyphs, `$1` for `!` and so on.
+    pub nags: Vec<u8>,
+    /// A comment in front of the move, e.g. at the start of a variation.
+    pub comment_before: Option<String>,
+    pub comment: Option<String>,
+    /// The moves that can follow: the main line first, then the variations.
+    pub children: Vec<Node>,
+}
+
+#[derive(Clone)]
+pub struct Game {
+    /// Tag pairs in file order.
+    pub tags: Vec<(String, String)>,
+    /// The position before the first move, set up by the `FEN` tag if any.
+    pub start: Board,
+    /// The first move of the main line, then its alternatives.
+    pub moves: Vec<Node>,
+    pub result: PgnResult,
+}
+
+/// Where and why a PGN file could not be read. `line` is 1-based.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PgnError {
+    /// Not of the form `[Name "value"]`.
+    InvalidTag {
+        line: usize,
+    },
+    UnterminatedComment {
+        line: usize,
+    },
+    InvalidFen {
+        line: usize,
+        error: FenError,
+    },
+    InvalidMove {
+        line: usize,
+        error: MoveParseError,
+    },
+    /// A variation before any move, an unbalanced parenthesis, or a result
+    /// inside a variation.
+    InvalidVariation {
+        line: usize,
+    },
+    UnexpectedToken {
+        line: usize,
+        token: String,
+    },
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PgnError::InvalidTag { line } => write!(f, "line {}: invalid tag pair", line),
+            PgnError::UnterminatedComment { line } => {
+                write!(f, "line {}: comment is never closed", line)
+            }
+            PgnError::InvalidFen { line, error } => write!(f, "line {}: {}", line, error),
+            PgnError::InvalidMove { line, error } => write!(f, "line {}: {}", line, error),
+            PgnError::InvalidVariation { line } => {
+                write!(f, "line {}: misplaced variation", line)
+            }
+            PgnError::UnexpectedToken { line, token } => {
+                write!(f, "line {}: unexpected {:?}", line, token)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+impl Game {
+    pub fn mainline(&self) -> impl Iterator<Item = &Node> {
+        std::iter::successors(self.moves.first(), |node| node.children.first())
+    }
+
+    /// The game in PGN export format, with movetext lines wrapped at 79
+    /// characters.
+    pub fn to_pgn(&self) -> String {
+        let mut pgn = String::new();
+
+        for (name, value) in &self.tags {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            writeln!(pgn, "[{} \"{}\"]", name, value).unwrap();
+        }
+        if !self.tags.is_empty() {
+            pgn.push('\n');
+        }
+
+        let mut tokens = Vec::new();
+        write_moves(&mut self.start.clone(), &self.moves, true, &mut tokens);
+        tokens.push(self.result.as_str().to_string());
+
+        let mut width = 0;
+        for token in tokens {
+            if width > 0 && width + 1 + token.len() > LINE_WIDTH {
+                pgn.push('\n');
+                width = 0;
+            } else if width > 0 {
+                pgn.push(' ');
+                width += 1;
+            }
+            width += token.len();
+            pgn.push_str(&token);
+        }
+
+        pgn.push('\n');
+        pgn
+    }
+}
+
+/// Reads every game in `text`.
+pub fn read_games(text: &str) -> Result<Vec<Game>, PgnError> {
+    let mut parser = Parser {
+        text,
+        pos: 0,
+        line: 1,
+    };
+    let mut games = Vec::new();
+
+    while let Some(game) = parser.game()? {
+        games.push(game);
+    }
+
+    Ok(games)
+}
+
+/// Writes `roots`, the move played on `board` and its alternatives, and the
+/// rest of the main line after it. `board` is left as it was.
+fn write_moves(board: &mut Board, roots: &[Node], mut show_number: bool, tokens: &mut Vec<String>) {
+    let Some((main, alternatives)) = roots.split_first() else {
+        return;
+    };
+
+    if let Some(comment) = &main.comment_before {
+        push_comment(comment, tokens);
+        show_number = true;
+    }
+
+    match board.turn {
+        Color::White => tokens.push(format!("{}.", board.fullmove_number)),
+        Color::Black if show_number => tokens.push(format!("{}...", board.fullmove_number)),
+        Color::Black => {}
+    }
+
+    tokens.push(board.to_san(&main.mv));
+    tokens.extend(main.nags.iter().map(|nag| format!("${}", nag)));
+    if let Some(comment) = &main.comment {
+        push_comment(comment, tokens);
+    }
+
+    for alternative in alternatives {
+        let mut variation = Vec::new();
+        write_moves(
+            board,
+            std::slice::from_ref(alternative),
+            true,
+            &mut variation,
+        );
+
+        variation[0].insert(0, '(');
+        variation.last_mut().unwrap().push(')');
+        tokens.extend(variation);
+    }
+
+    board.make_move(&main.mv);
+    let interrupted = !alternatives.is_empty() || main.comment.is_some();
+    write_moves(board, &main.children, interrupted, tokens);
+    board.unmake_move(&main.mv);
+}
+
+/// Splits the comment into words so long comments wrap too.
+fn push_comment(comment: &str, tokens: &mut Vec<String>) {
+    let start = tokens.len();
+
+    tokens.extend(comment.split_whitespace().map(str::to_string));
+    if tokens.len() == start {
+        tokens.push(String::new());
+    }
+
+    tokens[start].insert(0, '{');
+    tokens.last_mut().unwrap().push('}');
+}
+
+enum Token<'a> {
+    Tag(&'a str, String),
+    Comment(String),
+    Nag(u8),
+    Open,
+    Close,
+    Result(PgnResult),
+    /// SAN with the move number and any `!`/`?` suffix already split off.
+    Move(&'a str, Option<u8>),
+}
+
+/// A move of a line being read, with the variations that replace it.
+struct Pending {
+    node: Node,
+    alternatives: Vec<Node>,
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    line: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn game(&mut self) -> Result<Option<Game>, PgnError> {
+        let mut tags = Vec::new();
+        let mut fen = None;
+
+        loop {
+            let saved = (self.pos, self.line);
+
+            match self.next_token()? {
+                Some((line, Token::Tag(name, value))) => {
+                    if name == "FEN" {
+                        fen = Some((line, value.clone()));
+                    }
+                    tags.push((name.to_string(), value));
+                }
+                Some(_) => {
+                    (self.pos, self.line) = saved;
+                    break;
+                }
+                None if tags.is_empty() => return Ok(None),
+                None => break,
+            }
+        }
+
+        let mut start = match fen {
+            Some((line, fen)) => {
+                Board::from_fen(&fen).map_err(|error| PgnError::InvalidFen { line, error })?
+            }
+            None => Board::default(),
+        };
+
+        let (moves, result) = self.line(&mut start, false)?;
+
+        Ok(Some(Game {
+            tags,
+            start,
+            moves,
+            result,
+        }))
+    }
+
+    /// Reads moves until the end of the variation or game, playing them on
+    /// `board` and taking them back again.
+    fn line(
+        &mut self,
+        board: &mut Board,
+        nested: bool,
+    ) -> Result<(Vec<Node>, PgnResult), PgnError> {
+        let mut played: Vec<Pending> = Vec::new();
+        let mut comment_before: Option<String> = None;
+
+        let result = loop {
+            let saved = (self.pos, self.line);
+            let (line, token) = match self.next_token()? {
+                Some((line, token)) => (line, Some(token)),
+                None => (self.line, None),
+            };
+            let misplaced = PgnError::InvalidVariation { line };
+
+            match token {
+                None | Some(Token::Result(_)) | Some(Token::Tag(..)) if nested => {
+                    return Err(misplaced)
+                }
+                Some(Token::Close) if !nested => return Err(misplaced),
+                None | Some(Token::Close) => break PgnResult::Unknown,
+                Some(Token::Result(result)) => break result,
+                // The next game starts without a result marker.
+                Some(Token::Tag(..)) => {
+                    (self.pos, self.line) = saved;
+                    break PgnResult::Unknown;
+                }
+                Some(Token::Comment(text)) => match played.last_mut() {
+                    Some(last) => append_comment(&mut last.node.comment, text),
+                    None => append_comment(&mut comment_before, text),
+                },
+                Some(Token::Nag(nag)) => {
+                    if let Some(last) = played.last_mut() {
+                        last.node.nags.push(nag);
+                    }
+                }
+                Some(Token::Open) => {
+                    let last = played.last_mut().ok_or(misplaced)?;
+
+                    board.unmake_move(&last.node.mv);
+                    let (alternatives, _) = self.line(board, true)?;
+                    board.make_move(&last.node.mv);
+
+                    last.alternatives.extend(alternatives);
+                }
+                Some(Token::Move(san, nag)) => {
+                    let mv = board
+                        .parse_san(san)
+                        .map_err(|error| PgnError::InvalidMove { line, error })?;
+                    board.make_move(&mv);
+
+                    played.push(Pending {
+                        node: Node {
+                            mv,
+                            nags: nag.into_iter().collect(),
+                            comment_before: comment_before.take(),
+                            comment: None,
+                            children: Vec::new(),
+                        },
+                        alternatives: Vec::new(),
+                    });
+                }
+            }
+        };
+
+        for pending in played.iter().rev() {
+            board.unmake_move(&pending.node.mv);
+        }
+
+        // Each move becomes the first child of the one before it, followed
+        // by the variations that replace it.
+        let mut roots = Vec::new();
+        for pending in played.into_iter().rev() {
+            let mut node = pending.node;
+            node.children = roots;
+            roots = std::iter::once(node).chain(pending.alternatives).collect();
+        }
+
+        Ok((roots, result))
+    }
+
+    /// The next token and the line it is on.
+    fn next_token(&mut self) -> Result<Option<(usize, Token<'a>)>, PgnError> {
+        loop {
+            // `%` at the start of a line escapes the whole line.
+            let line_start = self.pos == 0 || self.text[..self.pos].ends_with('\n');
+            match self.peek() {
+                None => return Ok(None),
+                Some('%') if line_start => self.skip_line(),
+                Some(c) if c.is_whitespace() => {
+                    self.bump();
+                }
+                Some(_) => break,
+            }
+        }
+
+        let line = self.line;
+        let token = match self.bump().unwrap() {
+            '[' => self.tag()?,
+            '{' => {
+                let start = self.pos;
+                loop {
+                    match self.bump() {
+                        Some('}') => break,
+                        Some(_) => {}
+                        None => return Err(PgnError::UnterminatedComment { line }),
+                    }
+                }
+                Token::Comment(normalize(&self.text[start..self.pos - 1]))
+            }
+            ';' => {
+                let start = self.pos;
+                self.skip_line();
+                Token::Comment(normalize(&self.text[start..self.pos]))
+            }
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '$' => {
+                let digits = self.symbol();
+                let nag = digits.parse().map_err(|_| PgnError::UnexpectedToken {
+                    line,
+                    token: format!("${}", digits),
+                })?;
+                Token::Nag(nag)
+            }
+            _ => {
+                self.pos -= 1;
+                let symbol = self.symbol();
+                if let Some(result) = PgnResult::parse(symbol) {
+                    return Ok(Some((line, Token::Result(result))));
+                }
+
+                // Move numbers like `12.` and `12...`, possibly glued to
+                // the move.
+                let san = symbol.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+                let san = if san.len() < symbol.len()
+                    && symbol[..symbol.len() - san.len()].contains('.')
+                {
+                    san
+                } else {
+                    symbol
+                };
+                if san.is_empty() {
+                    return self.next_token();
+                }
+
+                let bare = san.trim_end_matches(['!', '?']);
+                let nag = match &san[bare.len()..] {
+                    "" => None,
+                    "!" => Some(1),
+                    "?" => Some(2),
+                    "!!" => Some(3),
+                    "??" => Some(4),
+                    "!?" => Some(5),
+                    "?!" => Some(6),
+                    _ => {
+                        return Err(PgnError::UnexpectedToken {
+                            line,
+                            token: san.to_string(),
+                        })
+                    }
+                };
+                Token::Move(bare, nag)
+            }
+        };
+
+        Ok(Some((line, token)))
+    }
+
+    /// The rest of `[Name "value"]` after the bracket.
+    fn tag(&mut self) -> Result<Token<'a>, PgnError> {
+        let invalid = PgnError::InvalidTag { line: self.line };
+
+        self.skip_spaces();
+        let name = self.symbol();
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(invalid);
+        }
+
+        self.skip_spaces();
+        if self.bump() != Some('"') {
+            return Err(invalid);
+        }
+
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => break,
+                Some('\\') => match self.bump() {
+                    Some(c @ ('"' | '\\')) => value.push(c),
+                    _ => return Err(invalid),
+                },
+                Some('\n') | None => return Err(invalid),
+                Some(c) => value.push(c),
+            }
+        }
+
+        self.skip_spaces();
+        if self.bump() != Some(']') {
+            return Err(invalid);
+        }
+
+        Ok(Token::Tag(name, value))
+    }
+
+    /// A run of characters up to whitespace or a delimiter.
+    fn symbol(&mut self) -> &'a str {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| !c.is_whitespace() && !"[]{}();$\"".contains(c))
+        {
+            self.bump();
+        }
+        &self.text[start..self.pos]
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek().is_some_and(|c| c == ' ' || c == '\t') {
+            self.bump();
+        }
+    }
+
+    fn skip_line(&mut self) {
+        while self.peek().is_some_and(|c| c != '\n') {
+            self.bump();
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+}
+
+fn normalize(comment: &str) -> String {
+    comment.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn append_comment(slot: &mut Option<String>, text: String) {
+    match slot {
+        Some(comment) => {
+            comment.push(' ');
+            comment.push_str(&text);
+        }
+        None => *slot = Some(text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAME: &str = r#"[Event "Casual \"blitz\" game"]
+[Site "?"]
+[White "A"]
+[Black "B"]
+[Result "1-0"]
+
+{Scholar's mate.} 1. e4 e5 2. Bc4 (2. Nf3 Nc6 (2... d6 3. d4) 3. Bb5 $1) 2... Nc6
+3. Qh5!? Nf6?? {Loses at once.} ; a line comment
+4. Qxf7# 1-0
+"#;
+
+    fn tag<'a>(game: &'a Game, name: &str) -> Option<&'a str> {
+        game.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn sans(board: &Board, nodes: &[Node]) -> Vec<String> {
+        nodes.iter().map(|node| board.to_san(&node.mv)).collect()
+    }
+
+    #[test]
+    fn test_read_game() {
+        let games = read_games(GAME).unwrap();
+        assert_eq!(games.len(), 1);
+
+        let game = &games[0];
+        assert_eq!(tag(game, "Event"), Some("Casual \"blitz\" game"));
+        assert_eq!(tag(game, "Result"), Some("1-0"));
+        assert_eq!(game.result, PgnResult::WhiteWins);
+
+        let mainline: Vec<String> = game.mainline().map(|node| node.mv.to_uci()).collect();
+        assert_eq!(
+            mainline,
+            ["e2e4", "e7e5", "f1c4", "b8c6", "d1h5", "g8f6", "h5f7"]
+        );
+
+        let first = &game.moves[0];
+        assert_eq!(first.comment_before.as_deref(), Some("Scholar's mate."));
+
+        // 2. Bc4 and its alternative 2. Nf3, which has 2... d6 next to 2... Nc6.
+        let mut board = game.start.clone();
+        board.make_move(&first.mv);
+        board.make_move(&first.children[0].mv);
+        let second = &first.children[0].children;
+        assert_eq!(sans(&board, second), ["Bc4", "Nf3"]);
+
+        board.make_move(&second[1].mv);
+        assert_eq!(sans(&board, &second[1].children), ["Nc6", "d6"]);
+        assert_eq!(second[1].children[0].children[0].nags, [1]);
+
+        let nodes: Vec<&Node> = game.mainline().collect();
+        assert_eq!(nodes[4].nags, [5]);
+        assert_eq!(nodes[5].nags, [4]);
+        assert_eq!(
+            nodes[5].comment.as_deref(),
+            Some("Loses at once. a line comment")
+        );
+    }
+
+    #[test]
+    fn test_write_game() {
+        let game = &read_games(GAME).unwrap()[0];
+
+        let expected = r#"[Event "Casual \"blitz\" game"]
+[Site "?"]
+[White "A"]
+[Black "B"]
+[Result "1-0"]
+
+{Scholar's mate.} 1. e4 e5 2. Bc4 (2. Nf3 Nc6 (2... d6 3. d4) 3. Bb5 $1) 2...
+Nc6 3. Qh5 $5 Nf6 $4 {Loses at once. a line comment} 4. Qxf7# 1-0
+"#;
+        assert_eq!(game.to_pgn(), expected);
+
+        let again = &read_games(&game.to_pgn()).unwrap()[0];
+        assert_eq!(again.tags, game.tags);
+        assert_eq!(again.moves, game.moves);
+        assert_eq!(again.result, game.result);
+    }
+
+    #[test]
+    fn test_long_lines_wrap() {
+        let long = "[Result \"*\"]\n\n1.Nf3 Nf6 2.Ng1 Ng8 3.Nf3 Nf6 4.Ng1 Ng8 5.Nf3 Nf6 6.Ng1 Ng8 \
+                    {a comment that is long enough to run past the end of the line by itself} *";
+        let game = &read_games(long).unwrap()[0];
+        let pgn = game.to_pgn();
+
+        assert!(pgn.lines().all(|line| line.len() <= LINE_WIDTH));
+        assert!(pgn.lines().count() > 3);
+        assert_eq!(read_games(&pgn).unwrap()[0].moves, game.moves);
+    }
+
+    #[test]
+    fn test_fen_tag_and_several_games() {
+        let text = r#"[FEN "4k3/8/8/8/8/8/4P3/4K3 b - - 0 40"]
+[SetUp "1"]
+
+40... Kd7 41. e4 *
+
+[White "Second"]
+1. d4 d5 1/2-1/2
+"#;
+        let games = read_games(text).unwrap();
+        assert_eq!(games.len(), 2);
+
+        let mainline: Vec<String> = games[0].mainline().map(|node| node.mv.to_uci()).collect();
+        assert_eq!(mainline, ["e8d7", "e2e4"]);
+        assert_eq!(games[0].result, PgnResult::Unknown);
+        assert!(games[0].to_pgn().contains("40... Kd7 41. e4 *"));
+
+        assert_eq!(tag(&games[1], "White"), Some("Second"));
+        assert_eq!(games[1].mainline().count(), 2);
+        assert_eq!(games[1].result, PgnResult::Draw);
+    }
+
+    #[test]
+    fn test_game_without_result_is_followed_by_the_next() {
+        let games = read_games("1. e4 e5\n\n[White \"x\"]\n1. d4 *").unwrap();
+
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].result, PgnResult::Unknown);
+        assert_eq!(games[1].mainline().count(), 1);
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let error = |text: &str| read_games(text).err().unwrap();
+
+        assert_eq!(
+            error("1. e4 e5\n2. Ke3 *"),
+            PgnError::InvalidMove {
+                line: 2,
+                error: MoveParseError::Illegal("Ke3".to_string())
+            }
+        );
+        assert_eq!(
+            error("[Event \"x]\n1. e4 *"),
+            PgnError::InvalidTag { line: 1 }
+        );
+        assert_eq!(
+            error("1. e4 {open\n\n*"),
+            PgnError::UnterminatedComment { line: 1 }
+        );
+        assert_eq!(error("(1. e4) *"), PgnError::InvalidVariation { line: 1 });
+        assert_eq!(
+            error("1. e4 (1. d4\n*"),
+            PgnError::InvalidVariation { line: 2 }
+        );
+        assert_eq!(error("1. e4 ) *"), PgnError::InvalidVariation { line: 1 });
+        assert!(matches!(
+            error("[FEN \"8/8/8/8/8/8/8/8 w - - 0 1\"]\n*"),
+            PgnError::InvalidFen { line: 1, .. }
+        ));
+        assert!(read_games("").unwrap().is_empty());
+    }
+}
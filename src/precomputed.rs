@@ -1,3 +1,4 @@
+#[cfg(test)]
 pub const NumSquaresToTheEdge: [[i32; 8]; 64] = [[7, 0, 0, 7, 0, 0, 7, 0], 
 [7, 0, 1, 6, 1, 0, 6, 0], 
 [7, 0, 2, 5, 2, 0, 5, 0], 
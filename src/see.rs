@@ -0,0 +1,168 @@
+use crate::{
+    bitboard::BitBoard,
+    board::{Board, Color, Piece},
+    consts::Square,
+    engine::piece_value,
+    moves::{Flags, Move},
+};
+
+/// Cheapest piece first, the order in which captures are made on a square.
+const CAPTURE_ORDER: [Piece; 6] = [
+    Piece::Pawn,
+    Piece::Knight,
+    Piece::Bishop,
+    Piece::Rook,
+    Piece::Queen,
+    Piece::King,
+];
+
+impl Board {
+    /// Static exchange evaluation: the material `mv` wins (or loses, if
+    /// negative) once both sides have made every profitable capture on its
+    /// destination, cheapest piece first. Sliders hidden behind a piece that
+    /// captures join in as it leaves. Pins are not taken into account.
+    pub fn see(&self, mv: &Move) -> i32 {
+        if mv.flags == Flags::Castling {
+            return 0;
+        }
+
+        let to = mv.to;
+        let mut occupied = self.occupied ^ mv.from.bb();
+        if mv.flags == Flags::EnPassant {
+            occupied ^= Square::from_index((mv.from as u8 & !7) | (to as u8 & 7)).bb();
+        }
+
+        // gains[d] is what the side making the d-th capture has won if the
+        // exchange stops right after it.
+        let mut gains = [0; 32];
+        gains[0] = mv.captured_piece.map_or(0, piece_value);
+        let mut on_square = mv.piece;
+        if let Some(promotion) = mv.promotion {
+            gains[0] += piece_value(promotion) - piece_value(Piece::Pawn);
+            on_square = promotion;
+        }
+
+        let mut attackers = self.attackers_to(to, occupied) & occupied;
+        let mut side = self.turn.opposite();
+        let mut depth = 0;
+
+        while let Some((piece, from)) = self.least_valuable_attacker(attackers, side) {
+            // The king may only take last, when nothing can take it back.
+            if piece == Piece::King && !(attackers & self.occupied_by(side.opposite())).is_empty() {
+                break;
+            }
+
+            depth += 1;
+            gains[depth] = piece_value(on_square) - gains[depth - 1];
+            on_square = piece;
+
+            occupied ^= from.bb();
+            attackers = self.attackers_to(to, occupied) & occupied;
+            side = side.opposite();
+        }
+
+        while depth > 0 {
+            gains[depth - 1] = -(-gains[depth - 1]).max(gains[depth]);
+            depth -= 1;
+        }
+
+        gains[0]
+    }
+
+    /// Whether `see(mv)` is at least `threshold`.
+    pub fn see_ge(&self, mv: &Move, threshold: i32) -> bool {
+        self.see(mv) >= threshold
+    }
+
+    fn least_valuable_attacker(
+        &self,
+        attackers: BitBoard,
+        color: Color,
+    ) -> Option<(Piece, Square)> {
+        CAPTURE_ORDER.into_iter().find_map(|piece| {
+            (attackers & self.pieces_of(color, piece))
+                .next_square()
+                .map(|sq| (piece, sq))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn see(fen: &str, uci: &str) -> i32 {
+        let board = Board::from_fen(fen).unwrap();
+        let mv = board.parse_uci_move(uci).unwrap();
+        board.see(&mv)
+    }
+
+    #[test]
+    fn test_see_simple_captures() {
+        // Undefended pawn.
+        assert_eq!(
+            see("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"),
+            100
+        );
+        // Defended pawn taken by a rook.
+        assert_eq!(
+            see("1k2r3/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5"),
+            100 - 500
+        );
+        // Equal trade.
+        assert_eq!(see("4k3/8/3r4/8/8/3R4/8/4K3 w - - 0 1", "d3d6"), 500);
+        assert_eq!(see("4k3/2p5/3r4/8/8/3R4/8/4K3 w - - 0 1", "d3d6"), 0);
+    }
+
+    #[test]
+    fn test_see_x_rays() {
+        // Pieces lined up behind the first attackers of both sides join in.
+        let fen = "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1";
+        assert_eq!(see(fen, "d3e5"), 100 - 320);
+
+        // Doubled rooks against a single defender.
+        assert_eq!(
+            see("3r2k1/3r4/8/8/3p4/8/3R4/3R2K1 w - - 0 1", "d2d4"),
+            100 - 500
+        );
+        assert_eq!(see("6k1/3r4/8/8/3p4/8/3R4/3R2K1 w - - 0 1", "d2d4"), 100);
+        assert_eq!(
+            see("6k1/3r4/8/4p3/3p4/8/3R4/3R2K1 w - - 0 1", "d2d4"),
+            100 - 500
+        );
+        assert_eq!(see("3r2k1/8/8/3n4/8/8/3R4/3R2K1 w - - 0 1", "d2d5"), 320);
+
+        // A queen behind a bishop joins in diagonally.
+        assert_eq!(
+            see("6k1/1b6/8/3n4/8/1B6/8/6K1 w - - 0 1", "b3d5"),
+            320 - 330
+        );
+        assert_eq!(see("6k1/1b6/8/3n4/8/1B6/Q7/6K1 w - - 0 1", "b3d5"), 320);
+    }
+
+    #[test]
+    fn test_see_king_recaptures_last() {
+        // The king may take back only when the square is not defended.
+        assert_eq!(see("8/8/8/4k3/3p4/8/8/3R2K1 w - - 0 1", "d1d4"), 100 - 500);
+        assert_eq!(see("8/8/8/4k3/3p4/8/3Q4/3R2K1 w - - 0 1", "d2d4"), 100);
+    }
+
+    #[test]
+    fn test_see_special_moves() {
+        // En passant, where the captured pawn is not on the target square.
+        assert_eq!(see("4k3/8/8/3Pp3/8/8/8/4K3 w - e6 0 1", "d5e6"), 100);
+        assert_eq!(see("4k3/5p2/8/3Pp3/8/8/8/4K3 w - e6 0 1", "d5e6"), 0);
+        // Promotion gains the difference to a pawn and risks the new queen.
+        assert_eq!(see("4k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8q"), 800);
+        assert_eq!(see("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8q"), 800 - 900);
+        assert_eq!(see("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7b8q"), 500 + 800);
+        // Quiet moves only lose material.
+        assert_eq!(see("4k3/8/8/4p3/8/8/8/3RK3 w - - 0 1", "d1d4"), -500);
+        assert_eq!(see("r3k3/8/8/8/8/8/8/4K2R w Kq - 0 1", "e1g1"), 0);
+
+        let board = Board::from_fen("4k3/2p5/3r4/8/8/3R4/8/4K3 w - - 0 1").unwrap();
+        let mv = board.parse_uci_move("d3d6").unwrap();
+        assert!(board.see_ge(&mv, 0));
+        assert!(!board.see_ge(&mv, 1));
+    }
+}
@@ -0,0 +1,316 @@
+use crate::{
+    board::Board,
+    engine::MATE_BOUND,
+    moves::{Move, PackedMove},
+};
+
+pub const DEFAULT_HASH_MB: usize = 16;
+
+const BUCKET_SIZE: usize = 4;
+
+/// How the stored score relates to the true score of the position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    /// The score is exact (a PV node).
+    Exact,
+    /// The search failed high; the true score is at least this.
+    Lower,
+    /// The search failed low; the true score is at most this.
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Entry {
+    /// Upper half of the Zobrist hash; the lower half picks the bucket.
+    key: u32,
+    best_move: PackedMove,
+    score: i16,
+    depth: u8,
+    generation: u8,
+    /// `None` marks an empty slot.
+    bound: Option<Bound>,
+}
+
+type Bucket = [Entry; BUCKET_SIZE];
+
+/// A successful probe. The score is already adjusted to the probing ply.
+#[derive(Debug, Clone, Copy)]
+pub struct TTHit {
+    pub depth: u32,
+    pub bound: Bound,
+    pub score: i32,
+    best_move: PackedMove,
+}
+
+impl TTHit {
+    /// The stored best move, restored on `board`. After a key collision it
+    /// need not be legal there, so match it against the generated moves.
+    pub fn best_move(&self, board: &Board) -> Option<Move> {
+        if self.best_move.is_null() {
+            return None;
+        }
+
+        board.unpack_move(self.best_move)
+    }
+}
+
+/// Fixed-size transposition table keyed by `Board::zobrist_hash`.
+pub struct TranspositionTable {
+    buckets: Vec<Bucket>,
+    generation: u8,
+}
+
+impl TranspositionTable {
+    pub fn new(megabytes: usize) -> TranspositionTable {
+        let mut tt = TranspositionTable {
+            buckets: Vec::new(),
+            generation: 0,
+        };
+        tt.resize(megabytes);
+        tt
+    }
+
+    /// Reallocates the table to the largest power-of-two number of buckets
+    /// that fits in `megabytes`. All entries are lost.
+    pub fn resize(&mut self, megabytes: usize) {
+        let bytes = megabytes.max(1) * 1024 * 1024;
+        let count = bytes / std::mem::size_of::<Bucket>();
+        let count = 1usize << count.ilog2();
+
+        self.buckets = vec![Bucket::default(); count];
+        self.generation = 0;
+    }
+
+    pub fn clear(&mut self) {
+        self.buckets.fill(Bucket::default());
+        self.generation = 0;
+    }
+
+    /// Starts a new search, so entries from earlier ones age and get
+    /// replaced first.
+    pub fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+
+    pub fn probe(&self, hash: u64, ply: usize) -> Option<TTHit> {
+        let key = (hash >> 32) as u32;
+
+        self.bucket(hash).iter().find_map(|entry| {
+            let bound = entry.bound.filter(|_| entry.key == key)?;
+
+            Some(TTHit {
+                depth: entry.depth as u32,
+                bound,
+                score: score_from_tt(entry.score as i32, ply),
+                best_move: entry.best_move,
+            })
+        })
+    }
+
+    pub fn store(
+        &mut self,
+        hash: u64,
+        depth: u32,
+        bound: Bound,
+        score: i32,
+        best_move: Option<Move>,
+        ply: usize,
+    ) {
+        let key = (hash >> 32) as u32;
+        let generation = self.generation;
+        let bucket = self.bucket_mut(hash);
+
+        // Reuse the slot of the same position, then an empty one, then the
+        // one that is shallowest once age is taken into account.
+        let slot = match bucket
+            .iter()
+            .position(|e| e.bound.is_some() && e.key == key)
+        {
+            Some(idx) => idx,
+            None => match bucket.iter().position(|e| e.bound.is_none()) {
+                Some(idx) => idx,
+                None => (0..BUCKET_SIZE)
+                    .min_by_key(|&idx| {
+                        let age = generation.wrapping_sub(bucket[idx].generation) as i32;
+                        bucket[idx].depth as i32 - 8 * age
+                    })
+                    .unwrap(),
+            },
+        };
+
+        let entry = &mut bucket[slot];
+        let same_position = entry.bound.is_some() && entry.key == key;
+
+        // Keep a deeper result for the same position unless it is stale or
+        // the new one is exact.
+        if same_position
+            && entry.generation == generation
+            && bound != Bound::Exact
+            && entry.depth as u32 > depth + 2
+        {
+            return;
+        }
+
+        let best_move = match best_move {
+            Some(mv) => PackedMove::from(mv),
+            None if same_position => entry.best_move,
+            None => PackedMove::NULL,
+        };
+
+        *entry = Entry {
+            key,
+            best_move,
+            score: score_to_tt(score, ply) as i16,
+            depth: depth.min(u8::MAX as u32) as u8,
+            generation,
+            bound: Some(bound),
+        };
+    }
+
+    /// Permille of sampled slots filled during the current search, as
+    /// reported by UCI `info hashfull`.
+    pub fn hashfull(&self) -> u32 {
+        let sample = self.buckets.len().min(1000 / BUCKET_SIZE);
+
+        let used = self.buckets[..sample]
+            .iter()
+            .flatten()
+            .filter(|e| e.bound.is_some() && e.generation == self.generation)
+            .count();
+
+        (used * 1000 / (sample * BUCKET_SIZE)) as u32
+    }
+
+    fn bucket(&self, hash: u64) -> &Bucket {
+        &self.buckets[hash as usize & (self.buckets.len() - 1)]
+    }
+
+    fn bucket_mut(&mut self, hash: u64) -> &mut Bucket {
+        let len = self.buckets.len();
+        &mut self.buckets[hash as usize & (len - 1)]
+    }
+}
+
+/// Mate scores are stored relative to the node instead of the root so they
+/// stay correct when the position is reached at a different ply.
+fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE_BOUND {
+        score + ply as i32
+    } else if score <= -MATE_BOUND {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE_BOUND {
+        score - ply as i32
+    } else if score <= -MATE_BOUND {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::MATE;
+
+    use super::*;
+
+    #[test]
+    fn test_store_and_probe() {
+        let board = Board::default();
+        let moves = board.generate_legal_moves();
+        let mut tt = TranspositionTable::new(1);
+
+        assert!(tt.probe(board.zobrist_hash, 0).is_none());
+
+        tt.store(board.zobrist_hash, 5, Bound::Lower, 42, Some(moves[3]), 0);
+        let hit = tt.probe(board.zobrist_hash, 0).unwrap();
+
+        assert_eq!(hit.depth, 5);
+        assert_eq!(hit.bound, Bound::Lower);
+        assert_eq!(hit.score, 42);
+        assert_eq!(hit.best_move(&board), Some(moves[3]));
+
+        // Same bucket, different key.
+        assert!(tt.probe(board.zobrist_hash ^ (1 << 40), 0).is_none());
+    }
+
+    #[test]
+    fn test_mate_scores_are_relative_to_the_node() {
+        let mut tt = TranspositionTable::new(1);
+
+        // Mate in 3 plies from the root, found at ply 2.
+        tt.store(7, 1, Bound::Exact, MATE - 3, None, 2);
+
+        // Reached again at ply 4 it is 2 plies further from the root.
+        assert_eq!(tt.probe(7, 4).unwrap().score, MATE - 5);
+        assert_eq!(tt.probe(7, 2).unwrap().score, MATE - 3);
+
+        tt.store(9, 1, Bound::Exact, -MATE + 4, None, 4);
+        assert_eq!(tt.probe(9, 1).unwrap().score, -MATE + 1);
+    }
+
+    #[test]
+    fn test_keeps_best_move_without_new_one() {
+        let board = Board::default();
+        let moves = board.generate_legal_moves();
+        let mut tt = TranspositionTable::new(1);
+
+        tt.store(board.zobrist_hash, 2, Bound::Exact, 0, Some(moves[0]), 0);
+        tt.store(board.zobrist_hash, 3, Bound::Upper, -10, None, 0);
+
+        let hit = tt.probe(board.zobrist_hash, 0).unwrap();
+        assert_eq!(hit.bound, Bound::Upper);
+        assert_eq!(hit.best_move(&board), Some(moves[0]));
+    }
+
+    #[test]
+    fn test_replaces_shallowest_and_oldest_entries() {
+        let mut tt = TranspositionTable::new(1);
+        let stride = tt.buckets.len() as u64;
+        // Keys that all land in bucket 0.
+        let hash = |i: u64| (i + 1) << 32 | (i * stride) & 0xffff_ffff;
+
+        for i in 0..BUCKET_SIZE as u64 {
+            tt.store(hash(i), 10 - i as u32, Bound::Exact, 0, None, 0);
+        }
+        tt.store(hash(10), 8, Bound::Exact, 0, None, 0);
+
+        // The depth 7 entry made room.
+        assert!(tt.probe(hash(3), 0).is_none());
+        assert!(tt.probe(hash(10), 0).is_some());
+
+        // A few searches later, fresh shallow entries push out the old
+        // deep ones rather than each other.
+        for _ in 0..3 {
+            tt.new_search();
+        }
+        tt.store(hash(11), 1, Bound::Exact, 0, None, 0);
+        tt.store(hash(12), 1, Bound::Exact, 0, None, 0);
+        assert!(tt.probe(hash(11), 0).is_some());
+        assert!(tt.probe(hash(12), 0).is_some());
+    }
+
+    #[test]
+    fn test_resize_clear_and_hashfull() {
+        let mut tt = TranspositionTable::new(3);
+        assert!(tt.buckets.len().is_power_of_two());
+        assert!(tt.buckets.len() * std::mem::size_of::<Bucket>() <= 3 * 1024 * 1024);
+
+        assert_eq!(tt.hashfull(), 0);
+        for i in 0..tt.buckets.len() as u64 {
+            tt.store(i | 1 << 32, 1, Bound::Exact, 0, None, 0);
+        }
+        assert_eq!(tt.hashfull(), 1000 / BUCKET_SIZE as u32);
+
+        tt.new_search();
+        assert_eq!(tt.hashfull(), 0);
+
+        tt.clear();
+        assert!(tt.probe(1 << 32, 0).is_none());
+    }
+}
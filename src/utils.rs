@@ -1,213 +1,120 @@
 use crate::{
     bitboard::BitBoard,
-    board::{self, Board, Color},
-    consts::{
-        File, Rank, Square, BISHOP_ATTACKS, DIRECTION_OFFSETS, KING_ATTACKS, KING_MOVES, KNIGHT_ATTACKS, PAWN_ATTACKS, ROOK_ATTACKS
-    },
-    sliding_pieces::{get_bishop_moves, get_rook_moves},
+    board::{Board, Color, Piece},
+    consts::{Square, BETWEEN_MASKS, KING_ATTACKS, KNIGHT_ATTACKS, PAWN_ATTACKS},
+    sliding_pieces::{get_bishop_moves, get_queen_moves, get_rook_moves},
 };
 
+/// Squares a `piece` of `color` on `sq` attacks, with sliders stopped by
+/// `occupied`.
+pub fn piece_attacks(piece: Piece, color: Color, sq: Square, occupied: BitBoard) -> BitBoard {
+    match piece {
+        Piece::Pawn => BitBoard(PAWN_ATTACKS[color as usize][sq as usize]),
+        Piece::Knight => BitBoard(KNIGHT_ATTACKS[sq as usize]),
+        Piece::Bishop => get_bishop_moves(sq, occupied),
+        Piece::Rook => get_rook_moves(sq, occupied),
+        Piece::Queen => get_queen_moves(sq, occupied),
+        Piece::King => KING_ATTACKS[sq as usize],
+    }
+}
+
+/// a1, c1, ..., h8: the squares a bishop starting on a1 can reach.
+const DARK_SQUARES: BitBoard = BitBoard(0xAA55_AA55_AA55_AA55);
+
 impl Board {
-    pub fn from_fen(fen: &str) -> Result<Board, &'static str> {
-        let parts: Vec<&str> = fen.split(' ').collect();
-        if parts.len() != 6 {
-            return Err("FEN must have 6 parts");
-        }
+    pub fn is_square_attacked(&self, sq: Square, attacking_color: Color) -> bool {
+        self.is_square_attacked_through(sq, attacking_color, self.occupied)
+    }
 
-        let piece_placement = parts[0];
-        let active_color = parts[1];
-        let castling_rights_str = parts[2];
-        let en_passant_sq_str = parts[3];
-        let halfmove_clock_str = parts[4];
-        let fullmove_number_str = parts[5];
-
-        let mut board = Board {
-            white_pawns: BitBoard(0),
-            white_knights: BitBoard(0),
-            white_bishops: BitBoard(0),
-            white_rooks: BitBoard(0),
-            white_queens: BitBoard(0),
-            white_king: BitBoard(0),
-            black_pawns: BitBoard(0),
-            black_knights: BitBoard(0),
-            black_bishops: BitBoard(0),
-            black_rooks: BitBoard(0),
-            black_queens: BitBoard(0),
-            black_king: BitBoard(0),
-            white_occupied: BitBoard(0),
-            black_occupied: BitBoard(0),
-            occupied: BitBoard(0),
-            empty: BitBoard(0),
-            turn: board::Color::White,
-            castling_rights: 0b1111,
-            en_passant_square: None,
-            halfmove_clock: 0,
-            fullmove_number: 1,
-            zobrist_hash: 0,
-            history: Vec::new(),
-        };
+    /// Like `is_square_attacked`, but sliders see through everything not in
+    /// `blockers`, e.g. the king that is about to step off their ray.
+    pub fn is_square_attacked_through(
+        &self,
+        sq: Square,
+        attacking_color: Color,
+        blockers: BitBoard,
+    ) -> bool {
+        !(self.attackers_to(sq, blockers) & self.occupied_by(attacking_color)).is_empty()
+    }
 
-        let mut rank = 7;
-        let mut file = 0;
-        for c in piece_placement.chars() {
-            if c == '/' {
-                rank -= 1;
-                file = 0;
-            } else if c.is_digit(10) {
-                file += c.to_digit(10).unwrap() as u8;
-            } else {
-                let square_idx = rank * 8 + file;
-                let bit = 1u64 << square_idx;
-                match c {
-                    'P' => board.white_pawns.0 |= bit,
-                    'N' => board.white_knights.0 |= bit,
-                    'B' => board.white_bishops.0 |= bit,
-                    'R' => board.white_rooks.0 |= bit,
-                    'Q' => board.white_queens.0 |= bit,
-                    'K' => board.white_king.0 |= bit,
-                    'p' => board.black_pawns.0 |= bit,
-                    'n' => board.black_knights.0 |= bit,
-                    'b' => board.black_bishops.0 |= bit,
-                    'r' => board.black_rooks.0 |= bit,
-                    'q' => board.black_queens.0 |= bit,
-                    'k' => board.black_king.0 |= bit,
-                    _ => return Err("Invalid FEN string: Invalid piece character"),
-                }
-                file += 1;
-            }
-        }
+    /// Pieces of both colors attacking `sq`, with sliders stopped only by
+    /// `occupied`. Pieces missing from `occupied` still show up; mask them
+    /// out when they are meant to be gone.
+    pub fn attackers_to(&self, sq: Square, occupied: BitBoard) -> BitBoard {
+        let both =
+            |piece| self.pieces_of(Color::White, piece) | self.pieces_of(Color::Black, piece);
+        let diagonal = both(Piece::Bishop) | both(Piece::Queen);
+        let straight = both(Piece::Rook) | both(Piece::Queen);
+
+        // A pawn attacks `sq` from where a pawn of the other color on `sq`
+        // would attack.
+        (piece_attacks(Piece::Pawn, Color::Black, sq, occupied)
+            & self.pieces_of(Color::White, Piece::Pawn))
+            | (piece_attacks(Piece::Pawn, Color::White, sq, occupied)
+                & self.pieces_of(Color::Black, Piece::Pawn))
+            | (piece_attacks(Piece::Knight, Color::White, sq, occupied) & both(Piece::Knight))
+            | (piece_attacks(Piece::King, Color::White, sq, occupied) & both(Piece::King))
+            | (get_bishop_moves(sq, occupied) & diagonal)
+            | (get_rook_moves(sq, occupied) & straight)
+    }
 
-        board.white_occupied = board.white_pawns
-            | board.white_knights
-            | board.white_bishops
-            | board.white_rooks
-            | board.white_queens
-            | board.white_king;
-        board.black_occupied = board.black_pawns
-            | board.black_knights
-            | board.black_bishops
-            | board.black_rooks
-            | board.black_queens
-            | board.black_king;
-
-        board.occupied = board.white_occupied | board.black_occupied;
-        board.empty = !board.occupied;
-
-        if active_color == "w" {
-            board.turn = Color::White;
-        } else if active_color == "b" {
-            board.turn = Color::Black;
-        } else {
-            return Err("Invalid FEN string: Invalid active color");
-        }
+    /// Squares the piece on `sq` attacks, or nothing if the square is empty.
+    pub fn attacks_from(&self, sq: Square) -> BitBoard {
+        self.piece_on_square(sq)
+            .map_or(BitBoard::EMPTY, |(piece, color)| {
+                piece_attacks(piece, color, sq, self.occupied)
+            })
+    }
 
-        for c in castling_rights_str.chars() {
-            match c {
-                'K' => board.castling_rights |= 0b0001, // White kingside
-                'Q' => board.castling_rights |= 0b0010, // White queenside
-                'k' => board.castling_rights |= 0b0100, // Black kingside
-                'q' => board.castling_rights |= 0b1000, // Black queenside
-                '-' => continue,
-                _ => return Err("Invalid FEN string: Invalid castling rights"),
-            }
-        }
+    /// Every square attacked by at least one piece of `color`.
+    pub fn attacks_by(&self, color: Color) -> BitBoard {
+        self.occupied_by(color)
+            .into_iter()
+            .fold(BitBoard::EMPTY, |attacks, sq| {
+                attacks | self.attacks_from(sq)
+            })
+    }
 
-        if en_passant_sq_str != "-" {
-            if en_passant_sq_str.len() != 2 {
-                return Err("Invalid FEN string: Invalid en passant square");
+    /// Enemy pieces giving check to the side to move.
+    pub fn checkers(&self) -> BitBoard {
+        match self.pieces_of(self.turn, Piece::King).next_square() {
+            Some(king) => {
+                self.attackers_to(king, self.occupied) & self.occupied_by(self.turn.opposite())
             }
-            let file_char = en_passant_sq_str.chars().next().unwrap();
-            let rank_char = en_passant_sq_str.chars().nth(1).unwrap();
-
-            let file_idx = match file_char {
-                'a' => File::A,
-                'b' => File::B,
-                'c' => File::C,
-                'd' => File::D,
-                'e' => File::E,
-                'f' => File::F,
-                'g' => File::G,
-                'h' => File::H,
-                _ => return Err("Invalid FEN string: Invalid en passant square"),
-            };
-
-            let rank_idx = match rank_char.to_digit(10) {
-                Some(1) => Rank::First,
-                Some(2) => Rank::Second,
-                Some(3) => Rank::Third,
-                Some(4) => Rank::Fourth,
-                Some(5) => Rank::Fifth,
-                Some(6) => Rank::Sixth,
-                Some(7) => Rank::Seventh,
-                Some(8) => Rank::Eighth,
-                _ => return Err("Invalid FEN string: Invalid en passant square"),
-            };
-
-            board.en_passant_square = Some(Square::new(file_idx, rank_idx).unwrap());
+            None => BitBoard::EMPTY,
         }
-
-        board.halfmove_clock = halfmove_clock_str
-            .parse::<u8>()
-            .map_err(|_| "Invalid FEN string: Invalid halfmove clock")?;
-        board.fullmove_number = fullmove_number_str
-            .parse::<u16>()
-            .map_err(|_| "Invalid FEN string: Invalid fullmove number")?;
-
-        Ok(board)
     }
 
-    pub fn is_square_attacked(&self, sq: Square, attacking_color: Color) -> bool {
-        let (pawns, knights, bishops, rooks, queens, king) = match attacking_color {
-            Color::White => (
-                self.white_pawns,
-                self.white_knights,
-                self.white_bishops,
-                self.white_rooks,
-                self.white_queens,
-                self.white_king,
-            ),
-            Color::Black => (
-                self.black_pawns,
-                self.black_knights,
-                self.black_bishops,
-                self.black_rooks,
-                self.black_queens,
-                self.black_king,
-            ),
+    /// Pieces of `color` that are the only piece between their king and an
+    /// enemy bishop, rook or queen, and so may only move along that line.
+    pub fn pinned(&self, color: Color) -> BitBoard {
+        let Some(king) = self.pieces_of(color, Piece::King).next_square() else {
+            return BitBoard::EMPTY;
         };
 
-
-
-        if PAWN_ATTACKS[attacking_color.opposite() as usize][sq as usize] & pawns.0 != 0 {
-            return true;
-        }
-
-        if KNIGHT_ATTACKS[sq as usize] & knights.0 != 0 {
-            return true;
-        }
-
-        if KING_ATTACKS[sq as usize] & king != BitBoard::EMPTY {
-            return true;
-        }
-
-        let blockers = self.occupied;
-
-        if get_bishop_moves(sq, blockers) & (bishops | queens) != BitBoard::EMPTY {
-            return true;
-        }
-
-        if get_rook_moves(sq, blockers) & (rooks | queens) != BitBoard::EMPTY {
-            return true;
+        let them = color.opposite();
+        let diagonal = self.pieces_of(them, Piece::Bishop) | self.pieces_of(them, Piece::Queen);
+        let straight = self.pieces_of(them, Piece::Rook) | self.pieces_of(them, Piece::Queen);
+
+        // Sliders that would see the king if none of our pieces were in the
+        // way pin the piece between them when it is the only one there.
+        let theirs = self.occupied_by(them);
+        let snipers =
+            (get_bishop_moves(king, theirs) & diagonal) | (get_rook_moves(king, theirs) & straight);
+
+        let mut pinned = BitBoard::EMPTY;
+        for sniper in snipers {
+            let between = BitBoard(BETWEEN_MASKS[king as usize][sniper as usize]) & self.occupied;
+            if between.count() == 1 {
+                pinned |= between & self.occupied_by(color);
+            }
         }
 
-        false
+        pinned
     }
 
     pub fn is_king_in_check(&self, attacking_color: Color) -> bool {
-        let attacked_king = match attacking_color {
-            Color::White => self.black_king,
-            Color::Black => self.white_king,
-        };
+        let attacked_king = self.pieces_of(attacking_color.opposite(), Piece::King);
 
         if attacked_king.0 == 0 {
             return false;
@@ -225,76 +132,34 @@ impl Board {
     //     let north_ray =
     // }
 
+    /// Whether neither side can ever mate: bare kings, a single minor
+    /// piece, or only bishops that all stand on squares of one colour.
     pub fn is_insufficient_material(&self) -> bool {
-        let mut white_minor = 0;
-        let mut black_minor = 0;
-        let mut white_major = 0;
-        let mut black_major = 0;
-
-        if self.white_pawns != BitBoard::EMPTY || self.black_pawns != BitBoard::EMPTY {
-            return false; // Presence of pawns means insufficient material is not possible
-        }
+        let both =
+            |piece| self.pieces_of(Color::White, piece) | self.pieces_of(Color::Black, piece);
 
-        white_major += self.white_rooks.count() + self.white_queens.count();
-        black_major += self.black_rooks.count() + self.black_queens.count();
-
-        if white_major > 0 && black_major > 0 {
+        if !(both(Piece::Pawn) | both(Piece::Rook) | both(Piece::Queen)).is_empty() {
             return false;
         }
 
-        white_minor += self.white_knights.count() + self.white_bishops.count();
-        black_minor += self.black_knights.count() + self.black_bishops.count();
-
-        if white_minor == 0 && black_minor == 0 { return true; }
+        let knights = both(Piece::Knight);
+        let bishops = both(Piece::Bishop);
 
-        if white_minor == 1 && black_minor == 0 && self.white_queens.is_empty() && self.white_rooks.is_empty() { return true; }
-        if black_minor == 1 && white_minor == 0 && self.black_queens.is_empty() && self.black_rooks.is_empty() { return true; }
-
-        if white_minor == 1 && self.white_bishops.count() == 1 && black_minor == 0 { return true; }
-        if black_minor == 1 && self.black_bishops.count() == 1 && white_minor == 0 { return true; }
+        if (knights | bishops).count() <= 1 {
+            return true;
+        }
 
-        false
+        knights.is_empty()
+            && ((bishops & DARK_SQUARES).is_empty() || (bishops & !DARK_SQUARES).is_empty())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::moves::Move;
+    use crate::moves::MoveList;
 
     use super::*;
 
-    #[test]
-    fn test_from_fen() {
-        let board =
-            Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
-
-        assert_eq!(board.white_pawns.0, 0x000000000000FF00);
-        assert_eq!(board.white_knights.0, 0x0000000000000042);
-        assert_eq!(board.white_bishops.0, 0x0000000000000024);
-        assert_eq!(board.white_rooks.0, 0x0000000000000081);
-        assert_eq!(board.white_queens.0, 0x0000000000000008);
-        assert_eq!(board.white_king.0, 0x0000000000000010);
-
-        assert_eq!(board.black_pawns.0, 0x00FF000000000000);
-        assert_eq!(board.black_knights.0, 0x4200000000000000);
-        assert_eq!(board.black_bishops.0, 0x2400000000000000);
-        assert_eq!(board.black_rooks.0, 0x8100000000000000);
-        assert_eq!(board.black_queens.0, 0x0800000000000000);
-        assert_eq!(board.black_king.0, 0x1000000000000000);
-
-        assert_eq!(board.white_occupied.0, 0x000000000000FFFF);
-        assert_eq!(board.black_occupied.0, 0xFFFF000000000000);
-        assert_eq!(board.occupied.0, 0xFFFF00000000FFFF);
-        assert_eq!(board.empty.0, !0xFFFF00000000FFFF);
-
-        assert_eq!(board.turn, Color::White);
-        assert_eq!(board.castling_rights, 15);
-        assert_eq!(board.en_passant_square, None);
-        assert_eq!(board.halfmove_clock, 0);
-        assert_eq!(board.fullmove_number, 1);
-        assert_eq!(board.zobrist_hash, 0);
-    }
-
     #[test]
     fn test_is_white_king_in_check() {
         let board =
@@ -313,6 +178,41 @@ mod tests {
         assert_eq!(board.is_king_in_check(Color::White), true);
     }
 
+    #[test]
+    fn test_insufficient_material() {
+        let drawn = [
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/3BK3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/3NK3 b - - 0 1",
+            // Bishops on squares of one colour only.
+            "4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1",
+        ];
+        let playable = [
+            "4k3/8/8/8/8/8/8/3QK3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/3RK3 w - - 0 1",
+            "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/2BBK3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/2BNK3 w - - 0 1",
+            "2b1k3/8/8/8/8/8/8/2B1K3 w - - 0 1",
+            "4kn2/8/8/8/8/8/8/2B1K3 w - - 0 1",
+        ];
+
+        for fen in drawn {
+            assert!(
+                Board::from_fen(fen).unwrap().is_insufficient_material(),
+                "{}",
+                fen
+            );
+        }
+        for fen in playable {
+            assert!(
+                !Board::from_fen(fen).unwrap().is_insufficient_material(),
+                "{}",
+                fen
+            );
+        }
+    }
+
     #[test]
     fn test_is_king_in_check_even_tho_its_pined() {
         let board =
@@ -322,13 +222,102 @@ mod tests {
         assert_eq!(board.is_king_in_check(Color::White), false);
     }
 
+    fn squares(names: &[&str]) -> BitBoard {
+        names.iter().fold(BitBoard::EMPTY, |bb, name| {
+            bb | Square::from_name(name).unwrap().bb()
+        })
+    }
+
+    #[test]
+    fn test_attackers_to() {
+        let board =
+            Board::from_fen("r1bqkbnr/pppp1ppp/2n5/4p2Q/4P3/5N2/PPPP1PPP/RNB1KB1R b KQkq - 3 3")
+                .unwrap();
+
+        assert_eq!(
+            board.attackers_to(Square::E5, board.occupied),
+            squares(&["c6", "f3", "h5"])
+        );
+        assert_eq!(
+            board.attackers_to(Square::F7, board.occupied),
+            squares(&["e8", "h5"])
+        );
+        assert_eq!(
+            board.attackers_to(Square::D4, board.occupied),
+            squares(&["c6", "e5", "f3"])
+        );
+
+        // With the f7 pawn gone the queen also sees e8 along the diagonal.
+        let occupied = board.occupied ^ Square::F7.bb();
+        assert!(board.attackers_to(Square::E8, occupied).has(Square::H5));
+        assert!(!board
+            .attackers_to(Square::E8, board.occupied)
+            .has(Square::H5));
+    }
+
+    #[test]
+    fn test_attack_maps_agree_with_is_square_attacked() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+
+            for color in [Color::White, Color::Black] {
+                let attacks = board.attacks_by(color);
+                for index in 0..64 {
+                    let sq = Square::from_index(index);
+                    assert_eq!(
+                        attacks.has(sq),
+                        board.is_square_attacked(sq, color),
+                        "{} {}",
+                        fen,
+                        sq
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_attacks_from() {
+        let board = Board::default();
+
+        assert_eq!(board.attacks_from(Square::G1), squares(&["e2", "f3", "h3"]));
+        assert_eq!(board.attacks_from(Square::E7), squares(&["d6", "f6"]));
+        assert_eq!(
+            board.attacks_from(Square::D1),
+            squares(&["c1", "c2", "d2", "e2", "e1"])
+        );
+        assert_eq!(board.attacks_from(Square::E4), BitBoard::EMPTY);
+    }
+
+    #[test]
+    fn test_checkers_and_pinned() {
+        // Double check from the knight and the rook, with the e-pawn pinned
+        // by the rook on e8.
+        let board = Board::from_fen("4r1k1/3n4/8/1B6/8/3n4/4P3/4K2r w - - 0 1").unwrap();
+        assert_eq!(board.checkers(), squares(&["d3", "h1"]));
+        assert_eq!(board.pinned(Color::White), squares(&["e2"]));
+        assert_eq!(board.pinned(Color::Black), BitBoard::EMPTY);
+
+        let board = Board::from_fen("4k3/3n4/8/1B6/8/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(board.checkers(), BitBoard::EMPTY);
+        assert_eq!(board.pinned(Color::Black), squares(&["d7"]));
+
+        // Two pieces on the line means neither is pinned.
+        let board = Board::from_fen("4k3/3n4/2p5/1B6/8/8/8/4K3 b - - 0 1").unwrap();
+        assert_eq!(board.pinned(Color::Black), BitBoard::EMPTY);
+    }
+
     #[test]
     fn test_king_moves_knight_near() {
         let board = Board::from_fen("r1bq1bnr/ppp2ppp/1nkp4/4p3/1K2P3/6PP/PPPP1P2/RNBQ1BNR w - - 3 8").unwrap();
 
-        let mut moves: Vec<Move> = Vec::new();
+        let mut moves = MoveList::new();
 
-        board.generate_king_moves(&mut moves);
+        board.generate_king_moves(&mut moves, &board.check_info());
 
         println!("Generated moves: {:?}", moves);
         assert_eq!(moves.len(), 4);
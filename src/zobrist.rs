@@ -0,0 +1,200 @@
+use crate::{
+    board::{Board, Color, Piece},
+    consts::Square,
+    rng::Rng,
+};
+
+/// Random keys for Zobrist hashing. They are generated at compile time from
+/// the fixed seed of `Rng`, so hashes are identical across runs and builds.
+pub struct ZobristKeys {
+    pub pieces: [[[u64; 64]; 6]; 2],
+    pub side_to_move: u64,
+    /// One key per combination of the four castling rights bits.
+    pub castling: [u64; 16],
+    pub en_passant_file: [u64; 8],
+}
+
+impl ZobristKeys {
+    const fn generate() -> ZobristKeys {
+        let mut rng = Rng::new();
+        let mut keys = ZobristKeys {
+            pieces: [[[0; 64]; 6]; 2],
+            side_to_move: 0,
+            castling: [0; 16],
+            en_passant_file: [0; 8],
+        };
+
+        let mut color = 0;
+        while color < 2 {
+            let mut piece = 0;
+            while piece < 6 {
+                let mut square = 0;
+                while square < 64 {
+                    keys.pieces[color][piece][square] = rng.next_u64();
+                    square += 1;
+                }
+                piece += 1;
+            }
+            color += 1;
+        }
+
+        keys.side_to_move = rng.next_u64();
+
+        let mut rights = 0;
+        while rights < 16 {
+            keys.castling[rights] = rng.next_u64();
+            rights += 1;
+        }
+
+        let mut file = 0;
+        while file < 8 {
+            keys.en_passant_file[file] = rng.next_u64();
+            file += 1;
+        }
+
+        keys
+    }
+
+    pub fn piece(&self, color: Color, piece: Piece, square: Square) -> u64 {
+        self.pieces[color as usize][piece as usize][square as usize]
+    }
+
+    pub fn en_passant(&self, square: Option<Square>) -> u64 {
+        square.map_or(0, |sq| self.en_passant_file[sq.file() as usize])
+    }
+}
+
+pub static ZOBRIST: ZobristKeys = ZobristKeys::generate();
+
+impl Board {
+    /// Computes the Zobrist hash of the position from scratch. `make_move`
+    /// and `unmake_move` keep `zobrist_hash` equal to this incrementally.
+    pub fn compute_hash(&self) -> u64 {
+        let mut hash = 0;
+
+        for square in self.occupied {
+            if let Some((piece, color)) = self.piece_on_square(square) {
+                hash ^= ZOBRIST.piece(color, piece, square);
+            }
+        }
+
+        if self.turn == Color::Black {
+            hash ^= ZOBRIST.side_to_move;
+        }
+
+        hash ^= ZOBRIST.castling[self.castling_rights as usize];
+        hash ^= ZOBRIST.en_passant(self.en_passant_square);
+
+        hash
+    }
+
+    /// Computes `pawn_key` from scratch: the piece keys of all pawns.
+    pub fn compute_pawn_key(&self) -> u64 {
+        let mut key = 0;
+
+        for color in [Color::White, Color::Black] {
+            for square in self.pieces_of(color, Piece::Pawn) {
+                key ^= ZOBRIST.piece(color, Piece::Pawn, square);
+            }
+        }
+
+        key
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Plays every legal move to `depth`, checking the incremental hash
+    /// against a full recomputation after each make and unmake.
+    fn check_hashes(board: &mut Board, depth: u32) {
+        if depth == 0 {
+            return;
+        }
+
+        for mv in board.generate_legal_moves() {
+            let before = (board.zobrist_hash, board.pawn_key);
+
+            board.make_move(&mv);
+            assert_eq!(board.zobrist_hash, board.compute_hash(), "after {:?}", mv);
+            assert_eq!(board.pawn_key, board.compute_pawn_key(), "after {:?}", mv);
+            check_hashes(board, depth - 1);
+            board.unmake_move(&mv);
+
+            assert_eq!(
+                (board.zobrist_hash, board.pawn_key),
+                before,
+                "after undoing {:?}",
+                mv
+            );
+        }
+    }
+
+    #[test]
+    fn test_keys_are_distinct() {
+        let mut keys: Vec<u64> = ZOBRIST.pieces.iter().flatten().flatten().copied().collect();
+        keys.push(ZOBRIST.side_to_move);
+        keys.extend_from_slice(&ZOBRIST.castling);
+        keys.extend_from_slice(&ZOBRIST.en_passant_file);
+
+        let count = keys.len();
+        keys.sort_unstable();
+        keys.dedup();
+
+        assert_eq!(keys.len(), count);
+        assert!(!keys.contains(&0));
+    }
+
+    #[test]
+    fn test_incremental_hash_matches_recomputed() {
+        let mut board =
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+        check_hashes(&mut board, 3);
+
+        let mut board = Board::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
+        check_hashes(&mut board, 4);
+    }
+
+    #[test]
+    fn test_transpositions_share_a_hash() {
+        let mut board1 = Board::default();
+        let mut board2 = Board::default();
+
+        let moves1 = ["g1f3", "g8f6", "b1c3"];
+        let moves2 = ["b1c3", "g8f6", "g1f3"];
+
+        for (board, moves) in [(&mut board1, moves1), (&mut board2, moves2)] {
+            for text in moves {
+                let mv = board
+                    .generate_legal_moves()
+                    .into_iter()
+                    .find(|mv| format!("{:?}{:?}", mv.from, mv.to).to_lowercase() == text)
+                    .unwrap();
+                board.make_move(&mv);
+            }
+        }
+
+        assert_eq!(board1.zobrist_hash, board2.zobrist_hash);
+        assert_ne!(board1.zobrist_hash, Board::default().zobrist_hash);
+        // Knight moves leave the pawn structure alone.
+        assert_eq!(board1.pawn_key, Board::default().pawn_key);
+    }
+
+    #[test]
+    fn test_null_move_hash() {
+        let mut board = Board::from_fen("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1").unwrap();
+        let fen = board.to_fen();
+        let hash = board.zobrist_hash;
+
+        board.make_null_move();
+        assert_eq!(board.zobrist_hash, board.compute_hash());
+        assert_eq!(board.en_passant_square, None);
+        assert_eq!(board.turn, Color::White);
+
+        board.unmake_null_move();
+        assert_eq!(board.zobrist_hash, hash);
+        assert_eq!(board.to_fen(), fen);
+    }
+}
@@ -0,0 +1,116 @@
+//! Perft regression suite over the positions in `data/perft.epd`, run
+//! through the `perft` subcommand of the built binary.
+//!
+//! The default run only checks counts up to `SHALLOW_NODES`. The full suite
+//! takes minutes even in release:
+//!
+//!     cargo test --release --test perft -- --ignored
+
+use std::process::Command;
+
+const EPD: &str = include_str!("../data/perft.epd");
+
+/// Largest node count checked by the default run.
+const SHALLOW_NODES: u64 = 1_000_000;
+
+struct PerftCase {
+    fen: String,
+    /// `(depth, nodes)` pairs in file order.
+    counts: Vec<(u32, u64)>,
+}
+
+fn parse_epd(epd: &str) -> Vec<PerftCase> {
+    epd.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let mut fields = line.split(';');
+            let fen = fields.next().unwrap().trim().to_string();
+
+            let counts = fields
+                .map(|field| {
+                    let (depth, nodes) = field
+                        .trim()
+                        .strip_prefix('D')
+                        .and_then(|rest| rest.split_once(' '))
+                        .unwrap_or_else(|| panic!("bad perft field {:?} in {:?}", field, line));
+
+                    (depth.parse().unwrap(), nodes.trim().parse().unwrap())
+                })
+                .collect();
+
+            PerftCase { fen, counts }
+        })
+        .collect()
+}
+
+fn run_perft(fen: &str, depth: u32) -> u64 {
+    let output = Command::new(env!("CARGO_BIN_EXE_better-engine"))
+        .args(["perft", &depth.to_string(), fen])
+        .output()
+        .expect("failed to run better-engine");
+
+    assert!(
+        output.status.success(),
+        "perft {} {:?} failed: {}",
+        depth,
+        fen,
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .find_map(|line| line.strip_prefix("Nodes searched: "))
+        .and_then(|nodes| nodes.parse().ok())
+        .expect("no node count in perft output")
+}
+
+fn check_suite(max_nodes: u64) {
+    let mut failures = Vec::new();
+
+    for case in parse_epd(EPD) {
+        for &(depth, expected) in case.counts.iter().filter(|&&(_, nodes)| nodes <= max_nodes) {
+            let nodes = run_perft(&case.fen, depth);
+            if nodes != expected {
+                failures.push(format!(
+                    "{} depth {}: {} nodes, expected {}",
+                    case.fen, depth, nodes, expected
+                ));
+            }
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "perft mismatches:\n{}",
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn test_epd_parses() {
+    let cases = parse_epd(EPD);
+
+    assert!(cases.len() >= 20);
+    assert_eq!(cases[0].counts[0], (1, 20));
+    assert!(cases.iter().all(|case| {
+        case.fen.split(' ').count() == 6
+            && case
+                .counts
+                .iter()
+                .enumerate()
+                .all(|(i, &(depth, _))| depth == i as u32 + 1)
+    }));
+}
+
+#[test]
+fn test_perft_shallow() {
+    check_suite(SHALLOW_NODES);
+}
+
+#[test]
+#[ignore = "takes minutes; run with --release -- --ignored"]
+fn test_perft_full() {
+    check_suite(u64::MAX);
+}